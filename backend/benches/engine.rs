@@ -0,0 +1,69 @@
+//! Engine benchmarks. Compare against an earlier commit with
+//! `cargo bench --bench engine -- --save-baseline before` there, then
+//! `cargo bench --bench engine -- --baseline before` here.
+
+use backend::models::{BotLevel, Game, GameType, Move, Player, Position};
+use backend::solver;
+use backend::transposition::TranspositionTable;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// A quiet middle game with no immediate wins or double threats, so the bot has to search.
+fn middle_game() -> Game {
+    let mut game = Game::new(Uuid::nil(), Player::X, GameType::Bot, 15);
+    game.x = Some(Uuid::new_v4());
+    game.o = Some(Uuid::nil());
+    let x = [(7, 7), (8, 8), (6, 8), (9, 6), (5, 9)];
+    let o = [(7, 8), (8, 7), (6, 6), (10, 5)];
+    for (i, &(col, row)) in x.iter().enumerate() {
+        game.play(&Move::new(Player::X, Position::new(col, row)))
+            .unwrap();
+        if let Some(&(col, row)) = o.get(i) {
+            game.play(&Move::new(Player::O, Position::new(col, row)))
+                .unwrap();
+        }
+    }
+    game
+}
+
+fn find_bot_move(c: &mut Criterion) {
+    let game = middle_game();
+    let mut settings = BotLevel::Casual.settings();
+    settings.blunder_rate = 0.0;
+    // Keep threat-space search out of it so the search itself is measured.
+    settings.vcf_depth = 0;
+    settings.vct_depth = 0;
+    settings.time_budget = Duration::from_secs(60);
+    let cancelled = AtomicBool::new(false);
+    c.bench_function("find_bot_move depth 2", |b| {
+        b.iter(|| {
+            let mut game = game.clone();
+            let tt = TranspositionTable::default();
+            black_box(game.find_bot_move(&settings, &cancelled, &tt))
+        })
+    });
+}
+
+fn check_winning_move(c: &mut Criterion) {
+    let game = middle_game();
+    let stones: Vec<Position> = game.moves.iter().map(|mv| mv.position).collect();
+    c.bench_function("check_winning_move", |b| {
+        b.iter(|| {
+            for pos in &stones {
+                black_box(game.check_winning_move(pos).unwrap());
+            }
+        })
+    });
+}
+
+fn find_vct(c: &mut Criterion) {
+    let game = middle_game();
+    c.bench_function("find_vct depth 3", |b| {
+        b.iter(|| black_box(solver::find_vct(&game, Player::O, 3)))
+    });
+}
+
+criterion_group!(benches, find_bot_move, check_winning_move, find_vct);
+criterion_main!(benches);
@@ -0,0 +1,2 @@
+-- Add migration script here
+alter table game add column board_size smallint not null default 15 check (board_size >= 9 AND board_size <= 30);
@@ -0,0 +1,3 @@
+-- Add migration script here
+create type rule_set as enum ('freestyle', 'standard', 'renju', 'caro');
+alter table game add column rule_set rule_set not null default 'freestyle';
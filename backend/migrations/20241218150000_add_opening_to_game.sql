@@ -0,0 +1,5 @@
+-- Add migration script here
+create type opening as enum ('free', 'pro', 'swap', 'swap2');
+create type opening_step as enum ('propose', 'choose', 'extend', 'choose_after_extend', 'done');
+alter table game add column opening opening not null default 'free';
+alter table game add column opening_step opening_step not null default 'done';
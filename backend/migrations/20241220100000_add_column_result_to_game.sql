@@ -0,0 +1,3 @@
+-- Add migration script here
+create type game_result as enum ('x_wins', 'o_wins', 'x_resigned', 'o_resigned', 'x_timed_out', 'o_timed_out', 'draw_full_board', 'draw_agreement');
+alter table game add column result game_result;
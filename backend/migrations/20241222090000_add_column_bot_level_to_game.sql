@@ -0,0 +1,3 @@
+-- Add migration script here
+create type bot_level as enum ('beginner', 'casual', 'strong', 'expert');
+alter table game add column bot_level bot_level;
@@ -0,0 +1,2 @@
+-- Add migration script here
+alter table game add column seed bigint not null default 0;
@@ -0,0 +1,11 @@
+-- Add migration script here
+create table hint (
+    game_id uuid not null references game(id) on delete cascade,
+    user_id uuid not null,
+    turn int not null check (turn >= 0 AND turn <= 255),
+    created_at timestamptz default now()
+);
+
+create index idx_game_id_hint on hint(game_id);
+
+alter table hint enable row level security;
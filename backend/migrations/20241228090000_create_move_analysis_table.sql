@@ -0,0 +1,20 @@
+-- Add migration script here
+create type move_class as enum ('best', 'good', 'inaccuracy', 'mistake', 'blunder', 'missed_win');
+
+create table move_analysis (
+    game_id uuid not null references game(id) on delete cascade,
+    turn int not null check (turn >= 0 AND turn <= 255),
+    player player not null,
+    row smallint not null check (row >= 0 AND row <= 255),
+    col smallint not null check (col >= 0 AND col <= 255),
+    best_row smallint not null check (best_row >= 0 AND best_row <= 255),
+    best_col smallint not null check (best_col >= 0 AND best_col <= 255),
+    best_score int not null,
+    played_score int not null,
+    class move_class not null,
+    unique (game_id, turn)
+);
+
+create index idx_game_id_move_analysis on move_analysis(game_id);
+
+alter table move_analysis enable row level security;
@@ -0,0 +1,3 @@
+-- Add migration script here
+create index idx_x_game on game(x);
+create index idx_o_game on game(o);
@@ -0,0 +1,2 @@
+-- Add migration script here
+alter table game add column invite_code text unique;
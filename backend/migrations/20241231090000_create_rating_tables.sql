@@ -0,0 +1,28 @@
+-- Add migration script here
+create table rating (
+    user_id uuid primary key,
+    rating double precision not null default 1500,
+    deviation double precision not null default 350,
+    volatility double precision not null default 0.06,
+    games int not null default 0,
+    updated_at timestamptz not null default now()
+);
+
+create index idx_rating_rating on rating(rating desc);
+
+alter table rating enable row level security;
+
+create table rating_history (
+    game_id uuid not null references game(id) on delete cascade,
+    user_id uuid not null,
+    rating double precision not null,
+    deviation double precision not null,
+    volatility double precision not null,
+    change double precision not null,
+    created_at timestamptz not null default now(),
+    primary key (game_id, user_id)
+);
+
+create index idx_user_id_rating_history on rating_history(user_id, created_at desc);
+
+alter table rating_history enable row level security;
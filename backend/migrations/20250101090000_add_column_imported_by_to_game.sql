@@ -0,0 +1,3 @@
+-- Add migration script here
+alter table game add column imported_by uuid;
+create index idx_imported_by_game on game(imported_by);
@@ -0,0 +1,4 @@
+-- Add migration script here
+-- A game on the largest board, 30x30, can last 900 plies.
+alter table game_move drop constraint game_move_turn_check;
+alter table game_move add constraint game_move_turn_check check (turn >= 0 AND turn <= 900);
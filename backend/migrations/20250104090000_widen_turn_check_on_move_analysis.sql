@@ -0,0 +1,4 @@
+-- Add migration script here
+-- Same bound as game_move: a full 30x30 board.
+alter table move_analysis drop constraint move_analysis_turn_check;
+alter table move_analysis add constraint move_analysis_turn_check check (turn >= 0 AND turn <= 900);
@@ -0,0 +1,152 @@
+//! Post-game review: replays a finished game and grades every move against the engine's
+//! choice in the same position.
+//!
+//! Scores are the engine's evaluation for the player who moved, so a swing is how much that
+//! player gave away compared with the engine's move. A three is worth about 300 and a four
+//! about 400; forced wins score `WIN_SCORE` and up.
+
+use crate::models::{BotSettings, Game, Move, Position, WIN_SCORE};
+use crate::transposition::TranspositionTable;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use uuid::Uuid;
+
+/// Smallest swings for an inaccuracy, a mistake and a blunder.
+const INACCURACY_SWING: i32 = 50;
+const MISTAKE_SWING: i32 = 150;
+const BLUNDER_SWING: i32 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "move_class", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum MoveClass {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+    /// A forced win was on the board and the move let it go.
+    MissedWin,
+}
+
+impl Display for MoveClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveClass::Best => write!(f, "best"),
+            MoveClass::Good => write!(f, "good"),
+            MoveClass::Inaccuracy => write!(f, "inaccuracy"),
+            MoveClass::Mistake => write!(f, "mistake"),
+            MoveClass::Blunder => write!(f, "blunder"),
+            MoveClass::MissedWin => write!(f, "missed win"),
+        }
+    }
+}
+
+impl MoveClass {
+    pub fn classify(best_score: i32, played_score: i32) -> Self {
+        let swing = best_score - played_score;
+        if best_score >= WIN_SCORE && played_score < WIN_SCORE {
+            MoveClass::MissedWin
+        } else if swing <= 0 {
+            MoveClass::Best
+        } else if swing >= BLUNDER_SWING || (played_score <= -WIN_SCORE && best_score > -WIN_SCORE)
+        {
+            MoveClass::Blunder
+        } else if swing >= MISTAKE_SWING {
+            MoveClass::Mistake
+        } else if swing >= INACCURACY_SWING {
+            MoveClass::Inaccuracy
+        } else {
+            MoveClass::Good
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveAnalysis {
+    /// 1 for the first move, as in `game_move`.
+    pub turn: usize,
+    pub mv: Move,
+    /// The engine's move in the same position.
+    pub best: Position,
+    pub best_score: i32,
+    pub played_score: i32,
+    /// How much worse the move scored than the engine's; 0 when it was as good.
+    pub swing: i32,
+    pub class: MoveClass,
+}
+
+impl MoveAnalysis {
+    pub fn new(turn: usize, mv: Move, best: Position, best_score: i32, played_score: i32) -> Self {
+        Self {
+            turn,
+            mv,
+            best,
+            best_score,
+            played_score,
+            swing: (best_score - played_score).max(0),
+            class: MoveClass::classify(best_score, played_score),
+        }
+    }
+
+    /// The move's line in notation on a `board_size` board, e.g.
+    /// `9. Xo15 missed win (best l8, swing 120)`.
+    pub fn to_notation(&self, board_size: usize) -> String {
+        let line = format!(
+            "{}. {} {}",
+            self.turn,
+            self.mv.to_notation(board_size),
+            self.class
+        );
+        if self.class == MoveClass::Best {
+            return line;
+        }
+        format!(
+            "{line} (best {}, swing {})",
+            self.best.to_notation(board_size),
+            self.swing
+        )
+    }
+}
+
+/// Replays `game` from an empty board and grades each move with a search using `settings`.
+/// Stops early, returning the moves graded so far, when `cancelled` is set. Opening stones are
+/// graded like any other move.
+pub fn analyse(
+    game: &Game,
+    settings: &BotSettings,
+    cancelled: &AtomicBool,
+) -> Result<Vec<MoveAnalysis>> {
+    // The moves were checked when they were played; only the rule set matters for the
+    // engine, so the opening and the seats are not replayed.
+    let mut replay = Game::new(
+        game.room_id,
+        game.init_player,
+        game.game_type.clone(),
+        game.board_size,
+    );
+    replay.rule_set = game.rule_set;
+    replay.x = Some(Uuid::nil());
+    replay.o = Some(Uuid::nil());
+    let mut analysis = Vec::with_capacity(game.moves.len());
+    for (index, mv) in game.moves.iter().enumerate() {
+        if cancelled.load(Ordering::Relaxed) {
+            break;
+        }
+        // Stored scores are weighted for the side being graded, which alternates, so each
+        // move gets a fresh table.
+        let tt = TranspositionTable::default();
+        let (best, played) = replay.compare_move(mv.position, settings, cancelled, &tt);
+        analysis.push(MoveAnalysis::new(
+            index + 1,
+            *mv,
+            best.position,
+            best.score,
+            played.score,
+        ));
+        replay.play(mv)?;
+    }
+    Ok(analysis)
+}
@@ -1,20 +1,35 @@
+use crate::analysis;
 use crate::auth::{Claims, DecodingKeyProvider};
+use crate::book::OpeningBook;
 use crate::db::Db;
-use crate::models::{Game, GameEvent, GameStatus, GameType, Move, Player, PlayerStatus, User};
+use crate::matchmaking::{MatchEvent, MatchSettings, Queue, Ticket};
+use crate::models::{
+    BotLevel, BotSettings, Candidate, ForbiddenMove, Game, GameEvent, GameResult, GameStatus,
+    GameSummary, GameType, Move, Opening, OpeningStep, Player, PlayerStatus, Position, RuleSet,
+    Threat, User, DEFAULT_BOARD_SIZE, MAX_BOARD_SIZE, MIN_BOARD_SIZE,
+};
+use crate::profile::EvalProfiles;
+use crate::rating::{self, PlayerRating, Rating, RatingChange};
+use crate::record::{self, ImportOptions, RecordFormat};
+use crate::solver;
+use crate::transposition::TranspositionTable;
 use axum::extract::ws::{CloseFrame, Message, WebSocket};
-use axum::extract::{Path, State, WebSocketUpgrade};
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::extract::{Path, Query, State, WebSocketUpgrade};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
 use axum::{Json, Router};
 use futures::SinkExt;
 use futures::StreamExt;
 use jsonwebtoken::{decode, DecodingKey, Validation};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, sync::Arc};
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::{broadcast, mpsc, Mutex, Semaphore};
 use tower_http::cors::CorsLayer;
 use uuid::Uuid;
 
@@ -22,6 +37,91 @@ pub struct AppState {
     rooms: Mutex<HashMap<Uuid, RoomState>>,
     db: Db,
     decoding_key: DecodingKey,
+    book: OpeningBook,
+    profiles: EvalProfiles,
+    search_threads: usize,
+    debug: bool,
+    abandon_grace: Duration,
+    hints: HashMap<GameType, HintPolicy>,
+    /// Finished games waiting for analysis.
+    analysis_jobs: mpsc::UnboundedSender<Uuid>,
+    /// One permit per bot search allowed to run at once across all rooms.
+    search_permits: Semaphore,
+    /// Players waiting for a quick match.
+    matchmaking: Mutex<Queue>,
+}
+
+/// Server settings beyond the database and JWT secret.
+pub struct AppOptions {
+    /// Replies the bot plays in the first plies instead of searching.
+    pub opening_book: OpeningBook,
+    /// Evaluation profiles the bot levels play with.
+    pub eval_profiles: EvalProfiles,
+    /// Threads each bot search uses.
+    pub search_threads: usize,
+    /// Bot searches running at once across all rooms; later ones wait their turn.
+    pub max_concurrent_searches: usize,
+    /// Lets clients fix a game's RNG seed, and has the bot search to a fixed depth on one
+    /// thread, so bot games can be replayed move for move.
+    pub debug: bool,
+    /// How long a player may be away from a game under way before losing on time.
+    pub abandon_grace: Duration,
+    /// Hints allowed per game type. Types without an entry get none.
+    pub hints: HashMap<GameType, HintPolicy>,
+}
+
+/// How many hints a player may ask for in a game, and how many moves each lists.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct HintPolicy {
+    /// Hints per player and game; 0 turns hints off.
+    pub per_game: usize,
+    /// Most candidate moves one hint lists.
+    pub max_candidates: usize,
+}
+
+/// Chat prefix that highlights a coordinate instead of sending a message.
+const HIGHLIGHT_COMMAND: &str = "/highlight ";
+/// The level hints search at.
+const HINT_LEVEL: BotLevel = BotLevel::Strong;
+/// The level positions are evaluated at.
+const EVALUATION_LEVEL: BotLevel = BotLevel::Strong;
+/// The level finished games are analysed at.
+const ANALYSIS_LEVEL: BotLevel = BotLevel::Strong;
+/// How long a player may be away from a game under way before losing on time, unless
+/// configured otherwise.
+const ABANDON_GRACE: Duration = Duration::from_secs(60);
+/// How often waiting players are paired again as their bands widen.
+const MATCHMAKING_INTERVAL: Duration = Duration::from_secs(1);
+
+impl Default for AppOptions {
+    fn default() -> Self {
+        Self {
+            opening_book: OpeningBook::default(),
+            eval_profiles: EvalProfiles::default(),
+            search_threads: 1,
+            max_concurrent_searches: std::thread::available_parallelism()
+                .map_or(1, |cores| cores.get()),
+            debug: false,
+            abandon_grace: ABANDON_GRACE,
+            // Quick-match games are the competitive ones, so they get no hints.
+            hints: HashMap::from([
+                (
+                    GameType::Bot,
+                    HintPolicy {
+                        per_game: 10,
+                        max_candidates: 5,
+                    },
+                ),
+                (
+                    GameType::Private,
+                    HintPolicy {
+                        per_game: 3,
+                        max_candidates: 3,
+                    },
+                ),
+            ]),
+        }
+    }
 }
 
 impl DecodingKeyProvider for AppState {
@@ -34,6 +134,9 @@ impl DecodingKeyProvider for AppState {
 struct RoomState {
     users: HashSet<Uuid>,
     tx: broadcast::Sender<GameEvent>,
+    draw_offer: Option<Player>,
+    search_cancelled: Arc<AtomicBool>,
+    transposition_table: Arc<TranspositionTable>,
 }
 
 impl RoomState {
@@ -41,32 +144,67 @@ impl RoomState {
         Self {
             users: HashSet::new(),
             tx: broadcast::channel(32).0,
+            draw_offer: None,
+            search_cancelled: Arc::new(AtomicBool::new(false)),
+            transposition_table: Arc::default(),
         }
     }
 }
 
 impl AppState {
-    pub fn new(pool: PgPool, decoding_key: DecodingKey) -> Self {
+    pub fn new(
+        pool: PgPool,
+        decoding_key: DecodingKey,
+        options: AppOptions,
+        analysis_jobs: mpsc::UnboundedSender<Uuid>,
+    ) -> Self {
         Self {
             rooms: Mutex::new(HashMap::new()),
             db: Db::new(pool),
             decoding_key,
+            book: options.opening_book,
+            profiles: options.eval_profiles,
+            search_threads: options.search_threads.max(1),
+            debug: options.debug,
+            abandon_grace: options.abandon_grace,
+            hints: options.hints,
+            analysis_jobs,
+            search_permits: Semaphore::new(options.max_concurrent_searches.max(1)),
+            matchmaking: Mutex::default(),
         }
     }
 }
 
 pub fn app(pool: PgPool, jwt_secret: &str) -> Router {
+    app_with_options(pool, jwt_secret, AppOptions::default())
+}
+
+pub fn app_with_options(pool: PgPool, jwt_secret: &str, options: AppOptions) -> Router {
     let decoding_key = DecodingKey::from_secret(jwt_secret.as_bytes());
-    let state = AppState::new(pool, decoding_key);
+    let (analysis_jobs, jobs) = mpsc::unbounded_channel();
+    let state = Arc::new(AppState::new(pool, decoding_key, options, analysis_jobs));
+    tokio::spawn(analyse_games(state.clone(), jobs));
+    tokio::spawn(run_matchmaking(state.clone()));
     Router::new()
         //api
         .route("/api/health", get(health_check))
         .route("/api/games", post(play))
         .route("/api/rooms", get(get_rooms))
+        .route("/api/invites/:invite_code", post(join_private_game))
+        .route("/api/games/:game_id", get(get_finished_game))
+        .route("/api/users/:user_id/games", get(get_game_history))
+        .route("/api/users/:user_id/rating", get(get_rating))
+        .route("/api/leaderboard", get(get_leaderboard))
+        .route("/api/games/:game_id/hints", post(request_hint))
+        .route("/api/games/:game_id/analysis", get(get_analysis))
+        .route("/api/games/:game_id/export", get(export_game))
+        .route("/api/games/import", post(import_games))
+        .route("/api/evaluate", post(evaluate))
         //ws
         .route("/ws/rooms/:room_id", get(websocket_handler))
+        .route("/ws/matchmaking", get(matchmaking_handler))
         .layer(CorsLayer::permissive())
-        .with_state(Arc::new(state))
+        .with_state(state)
 }
 
 #[tracing::instrument(skip(state))]
@@ -87,6 +225,435 @@ async fn get_rooms(
     Ok(Json(rooms))
 }
 
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HintPayload {
+    /// Candidate moves wanted, capped by the game type's policy.
+    #[serde(default)]
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HintResponse {
+    pub candidates: Vec<Candidate>,
+    /// Hints the player has left in this game.
+    pub remaining: usize,
+}
+
+#[tracing::instrument(skip(state, _claims))]
+async fn request_hint(
+    State(state): State<Arc<AppState>>,
+    _claims @ Claims { sub, .. }: Claims,
+    Path(game_id): Path<Uuid>,
+    Json(HintPayload { count }): Json<HintPayload>,
+) -> Result<Json<HintResponse>, StatusCode> {
+    let game = state.db.get_game(&game_id).await.map_err(|error| {
+        tracing::error!(?error);
+        StatusCode::NOT_FOUND
+    })?;
+    let (candidates, remaining) = hint(&state, &game, sub, count).await?;
+    Ok(Json(HintResponse {
+        candidates,
+        remaining,
+    }))
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct EvaluatePayload {
+    /// The moves from an empty board, X first. Give either this or `board`.
+    #[serde(default)]
+    pub moves: Option<Vec<Position>>,
+    /// Board size for `moves`.
+    #[serde(default)]
+    pub board_size: Option<usize>,
+    /// A board diagram, as read by `Game::from_board`.
+    #[serde(default)]
+    pub board: Option<String>,
+    #[serde(default)]
+    pub rule_set: Option<RuleSet>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EvaluateResponse {
+    pub next_player: Player,
+    /// Static evaluation for `next_player`; positive is good for them.
+    pub evaluation: i32,
+    pub threats: Vec<Threat>,
+    /// A forced win for `next_player`, alternating with the forced replies.
+    pub forced_win: Option<Vec<Move>>,
+    pub best_move: Option<Position>,
+}
+
+/// Evaluates a position outside any game.
+#[tracing::instrument(skip(state, _claims))]
+async fn evaluate(
+    State(state): State<Arc<AppState>>,
+    _claims: Claims,
+    Json(EvaluatePayload {
+        moves,
+        board_size,
+        board,
+        rule_set,
+    }): Json<EvaluatePayload>,
+) -> Result<Json<EvaluateResponse>, StatusCode> {
+    let rule_set = rule_set.unwrap_or_default();
+    let mut game = match (moves, board) {
+        (Some(moves), None) => {
+            let board_size = board_size.unwrap_or(DEFAULT_BOARD_SIZE);
+            if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&board_size) {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            let mut game = Game::new(Uuid::nil(), Player::X, GameType::Bot, board_size);
+            game.rule_set = rule_set;
+            game.x = Some(Uuid::nil());
+            game.o = Some(Uuid::nil());
+            for pos in moves {
+                game.play(&Move::new(game.next_player, pos))
+                    .map_err(|_| StatusCode::BAD_REQUEST)?;
+            }
+            game
+        }
+        (None, Some(board)) => {
+            Game::from_board(&board, rule_set).map_err(|_| StatusCode::BAD_REQUEST)?
+        }
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+    if game.has_five() || game.is_full() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let settings = BotSettings {
+        threads: state.search_threads,
+        ..state.profiles.settings(EVALUATION_LEVEL)
+    };
+    let Ok(_permit) = state.search_permits.acquire().await else {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    };
+    let response = tokio::task::spawn_blocking(move || {
+        let next_player = game.next_player;
+        let (evaluation, threats) = game.static_evaluation(&settings);
+        let forced_win = solver::find_vcf(&game, next_player, settings.vcf_depth)
+            .or_else(|| solver::find_vct(&game, next_player, settings.vct_depth));
+        let tt = TranspositionTable::default();
+        let best_move = game.find_bot_move(&settings, &AtomicBool::new(false), &tt);
+        EvaluateResponse {
+            next_player,
+            evaluation,
+            threats,
+            forced_win,
+            best_move,
+        }
+    })
+    .await
+    .map_err(|error| {
+        tracing::error!(?error, "Evaluation failed");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(response))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FormatQuery {
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+/// A game's record: its moves in notation as plain text, or with `?format=psq`, `rif` or `sgf`
+/// a file for desktop tools. Games still in progress can be exported too, as the moves so far.
+/// RIF is only offered for 15x15 games.
+#[tracing::instrument(skip(state, _claims))]
+async fn export_game(
+    State(state): State<Arc<AppState>>,
+    _claims: Claims,
+    Path(game_id): Path<Uuid>,
+    Query(FormatQuery { format }): Query<FormatQuery>,
+) -> Result<Response, StatusCode> {
+    let game = state.db.get_game(&game_id).await.map_err(|error| {
+        tracing::error!(?error);
+        StatusCode::NOT_FOUND
+    })?;
+    let format = match format.as_deref() {
+        None | Some("text") => return Ok(game.notation().into_response()),
+        Some(format) => format
+            .parse::<RecordFormat>()
+            .map_err(|_| StatusCode::BAD_REQUEST)?,
+    };
+    if !format.supports(game.board_size) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let disposition = format!("attachment; filename=\"{game_id}.{}\"", format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        record::export(&game, format),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImportQuery {
+    pub format: String,
+    #[serde(default)]
+    pub rule_set: Option<RuleSet>,
+    /// Board size for formats without one, i.e. RIF.
+    #[serde(default)]
+    pub board_size: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportResponse {
+    pub games: Vec<Uuid>,
+}
+
+/// Stores the games of an uploaded PSQ, RIF or SGF record as finished games in the uploader's
+/// history. The uploader did not play them, so the seats are left empty and the games count
+/// as neither side's win. Nothing is stored unless every game replays.
+#[tracing::instrument(skip(state, _claims, body))]
+async fn import_games(
+    State(state): State<Arc<AppState>>,
+    _claims @ Claims { sub, .. }: Claims,
+    Query(ImportQuery {
+        format,
+        rule_set,
+        board_size,
+    }): Query<ImportQuery>,
+    body: String,
+) -> Result<Json<ImportResponse>, StatusCode> {
+    let format = format
+        .parse::<RecordFormat>()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let options = ImportOptions {
+        rule_set: rule_set.unwrap_or_default(),
+        board_size,
+    };
+    let games = record::import(&body, format, options).map_err(|error| {
+        tracing::info!(%error, "Invalid record");
+        StatusCode::BAD_REQUEST
+    })?;
+    let mut ids = vec![];
+    for game in games {
+        state.db.import_game(&game, &sub).await.map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        ids.push(game.id);
+    }
+    Ok(Json(ImportResponse { games: ids }))
+}
+
+/// The analysis of a finished game, as JSON or with `?format=text` one line per move in
+/// notation. Answers 202 with an empty list and queues the game when it has not been analysed
+/// yet.
+#[tracing::instrument(skip(state, _claims))]
+async fn get_analysis(
+    State(state): State<Arc<AppState>>,
+    _claims: Claims,
+    Path(game_id): Path<Uuid>,
+    Query(FormatQuery { format }): Query<FormatQuery>,
+) -> Result<Response, StatusCode> {
+    let game = state.db.get_game(&game_id).await.map_err(|error| {
+        tracing::error!(?error);
+        StatusCode::NOT_FOUND
+    })?;
+    if game.result.is_none() {
+        return Err(StatusCode::CONFLICT);
+    }
+    let analysis = state.db.get_analysis(&game_id).await.map_err(|error| {
+        tracing::error!(?error);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let status = if analysis.is_empty() && !game.moves.is_empty() {
+        if let Err(error) = state.analysis_jobs.send(game_id) {
+            tracing::error!(?error, "Error queueing analysis");
+        }
+        StatusCode::ACCEPTED
+    } else {
+        StatusCode::OK
+    };
+    match format.as_deref() {
+        None | Some("json") => Ok((status, Json(analysis)).into_response()),
+        Some("text") => {
+            let lines: Vec<String> = analysis
+                .iter()
+                .map(|line| line.to_notation(game.board_size))
+                .collect();
+            Ok((status, lines.join("\n")).into_response())
+        }
+        Some(_) => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+/// Games on one page of a user's history unless the client asks for another size.
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PageQuery {
+    /// Counted from 1.
+    #[serde(default)]
+    pub page: Option<usize>,
+    #[serde(default)]
+    pub page_size: Option<usize>,
+}
+
+impl PageQuery {
+    /// The page, its size and the offset of its first entry. Pages past the end are empty.
+    fn bounds(&self) -> Result<(usize, usize, usize), StatusCode> {
+        let page = self.page.unwrap_or(1);
+        let page_size = self.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+        if page == 0 || page_size == 0 || page_size > MAX_PAGE_SIZE {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Ok((page, page_size, (page - 1).saturating_mul(page_size)))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub games: Vec<GameSummary>,
+    pub page: usize,
+    pub page_size: usize,
+    /// Games in the whole history.
+    pub total: usize,
+}
+
+/// A user's finished and abandoned games, newest first.
+#[tracing::instrument(skip(state, _claims))]
+async fn get_game_history(
+    State(state): State<Arc<AppState>>,
+    _claims: Claims,
+    Path(user_id): Path<Uuid>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<HistoryPage>, StatusCode> {
+    let (page, page_size, offset) = query.bounds()?;
+    let (games, total) = state
+        .db
+        .get_game_history(&user_id, page_size as i64, offset as i64)
+        .await
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(HistoryPage {
+        games,
+        page,
+        page_size,
+        total: total as usize,
+    }))
+}
+
+/// Rating changes a profile shows.
+const RATING_HISTORY_LEN: i64 = 50;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RatingProfile {
+    #[serde(flatten)]
+    pub rating: PlayerRating,
+    /// Latest rated games first.
+    pub history: Vec<RatingChange>,
+}
+
+/// A player's rating and its latest changes. Players without rated games have the starting
+/// rating.
+#[tracing::instrument(skip(state, _claims))]
+async fn get_rating(
+    State(state): State<Arc<AppState>>,
+    _claims: Claims,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<RatingProfile>, StatusCode> {
+    let rating = state.db.get_rating(&user_id).await.map_err(|error| {
+        tracing::error!(?error);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let history = state
+        .db
+        .get_rating_history(&user_id, RATING_HISTORY_LEN)
+        .await
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let rating = rating.unwrap_or_else(|| PlayerRating {
+        user_id,
+        rating: Rating::default(),
+        games: 0,
+        provisional: true,
+    });
+    Ok(Json(RatingProfile { rating, history }))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BotRating {
+    pub level: BotLevel,
+    #[serde(flatten)]
+    pub rating: Rating,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub players: Vec<PlayerRating>,
+    pub page: usize,
+    pub page_size: usize,
+    /// Rated players in all.
+    pub total: usize,
+    /// The bot levels' fixed ratings, to compare players with.
+    pub bots: Vec<BotRating>,
+}
+
+#[tracing::instrument(skip(state, _claims))]
+async fn get_leaderboard(
+    State(state): State<Arc<AppState>>,
+    _claims: Claims,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Leaderboard>, StatusCode> {
+    let (page, page_size, offset) = query.bounds()?;
+    let (players, total) = state
+        .db
+        .get_leaderboard(page_size as i64, offset as i64)
+        .await
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let bots = [
+        BotLevel::Beginner,
+        BotLevel::Casual,
+        BotLevel::Strong,
+        BotLevel::Expert,
+    ]
+    .into_iter()
+    .map(|level| BotRating {
+        level,
+        rating: rating::bot_rating(level),
+    })
+    .collect();
+    Ok(Json(Leaderboard {
+        players,
+        page,
+        page_size,
+        total: total as usize,
+        bots,
+    }))
+}
+
+/// A finished game with its moves in the order they were played, for replay.
+#[tracing::instrument(skip(state, _claims))]
+async fn get_finished_game(
+    State(state): State<Arc<AppState>>,
+    _claims: Claims,
+    Path(game_id): Path<Uuid>,
+) -> Result<Json<Game>, StatusCode> {
+    let game = state.db.get_game(&game_id).await.map_err(|error| {
+        tracing::error!(?error);
+        StatusCode::NOT_FOUND
+    })?;
+    if game.result.is_none() && !matches!(game.status, GameStatus::Ended) {
+        return Err(StatusCode::CONFLICT);
+    }
+    Ok(Json(game))
+}
+
 #[tracing::instrument(skip(_state))]
 async fn health_check(State(_state): State<Arc<AppState>>) -> StatusCode {
     tracing::info!("Health check passed.");
@@ -96,79 +663,523 @@ async fn health_check(State(_state): State<Arc<AppState>>) -> StatusCode {
 #[derive(Debug, Deserialize, Serialize)]
 pub struct GamePayload {
     pub game_type: GameType,
+    #[serde(default)]
+    pub board_size: Option<usize>,
+    #[serde(default)]
+    pub rule_set: Option<RuleSet>,
+    #[serde(default)]
+    pub opening: Option<Opening>,
+    #[serde(default)]
+    pub bot_level: Option<BotLevel>,
+    /// The colour the creator wants to play. Defaults to X, which moves first.
+    #[serde(default)]
+    pub colour: Option<Player>,
+    /// Seed for the bot's random choices. Only accepted in debug mode.
+    #[serde(default)]
+    pub seed: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GameResponse {
-    room: Uuid,
+    pub room: Uuid,
+    /// For a new private game, the code that lets the invited player take the other seat.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite_code: Option<String>,
+}
+
+/// Letters and digits that cannot be mistaken for one another when read out.
+const INVITE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const INVITE_CODE_LEN: usize = 8;
+
+fn new_invite_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..INVITE_CODE_LEN)
+        .map(|_| char::from(INVITE_ALPHABET[rng.gen_range(0..INVITE_ALPHABET.len())]))
+        .collect()
 }
 
 #[tracing::instrument(skip(state, _claims))]
 async fn play(
     State(state): State<Arc<AppState>>,
     _claims @ Claims { sub, .. }: Claims,
-    Json(GamePayload { game_type }): Json<GamePayload>,
+    Json(GamePayload {
+        game_type,
+        board_size,
+        rule_set,
+        opening,
+        bot_level,
+        colour,
+        seed,
+    }): Json<GamePayload>,
 ) -> Result<Json<GameResponse>, StatusCode> {
     let user_id = sub;
+    let board_size = board_size.unwrap_or(DEFAULT_BOARD_SIZE);
+    if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&board_size) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if seed.is_some() && !state.debug {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let rule_set = rule_set.unwrap_or_default();
+    let opening = opening.unwrap_or_default();
+    let colour = colour.unwrap_or(Player::X);
+    let mut invite_code = None;
     let room_id = match game_type {
         GameType::Bot => {
+            if opening != Opening::Free {
+                return Err(StatusCode::BAD_REQUEST);
+            }
             let room_id = Uuid::new_v4();
-            let mut game: Game = Game::new(room_id, Player::X, GameType::Bot);
-            game.x = Some(user_id);
-            game.o = Some(Uuid::nil());
+            let mut game: Game = Game::new(room_id, Player::X, GameType::Bot, board_size);
+            game.rule_set = rule_set;
+            game.bot_level = Some(bot_level.unwrap_or_default());
+            game.seed = seed.unwrap_or(game.seed);
+            game.set_seat(colour, user_id);
+            game.set_seat(colour.opponent(), Uuid::nil());
             state.db.new_game(&game).await.map_err(|error| {
                 tracing::error!(?error);
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
             room_id
         }
-        GameType::Normal => {
-            #[allow(unused_assignments)]
-            let mut room = None;
-            {
-                let rooms = state.rooms.lock().await;
-                let rooms: Vec<Uuid> = { rooms.keys().map(|x| x.to_owned()).collect() };
-                match state.db.get_available_quick_games(&rooms).await {
-                    Ok(r) => room = Some(r),
-                    _ => room = None,
+        // Quick-match games are made by the matchmaking queue, see `matchmaking_websocket`.
+        GameType::Normal => return Err(StatusCode::BAD_REQUEST),
+        GameType::Private => {
+            let room_id = Uuid::new_v4();
+            let mut game = Game::new(room_id, Player::X, GameType::Private, board_size);
+            game.rule_set = rule_set;
+            game.set_opening(opening);
+            game.seed = seed.unwrap_or(game.seed);
+            game.set_seat(colour, user_id);
+            game.invite_code = Some(new_invite_code());
+            state.db.new_game(&game).await.map_err(|err| {
+                tracing::error!(?err);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            invite_code = game.invite_code;
+            room_id
+        }
+    };
+
+    Ok(Json(GameResponse {
+        room: room_id,
+        invite_code,
+    }))
+    // Ok(format!("/ws/rooms/{room_id}"))
+}
+
+/// Takes the empty seat of the private game `invite_code` belongs to. Answers 404 for an
+/// unknown code or a finished game, and 409 when both seats are already taken.
+#[tracing::instrument(skip(state, _claims))]
+async fn join_private_game(
+    State(state): State<Arc<AppState>>,
+    _claims @ Claims { sub, .. }: Claims,
+    Path(invite_code): Path<String>,
+) -> Result<Json<GameResponse>, StatusCode> {
+    let invite_code = invite_code.to_ascii_uppercase();
+    let room = state
+        .db
+        .join_private_game(&invite_code, &sub)
+        .await
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Some(room) = room {
+        return Ok(Json(GameResponse {
+            room,
+            invite_code: None,
+        }));
+    }
+    match state.db.invite_exists(&invite_code).await {
+        Ok(true) => Err(StatusCode::CONFLICT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            tracing::error!(?error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Saves the result, with the players' new ratings if the game is rated, announces it and
+/// queues the game for analysis.
+async fn finish_game(
+    state: &AppState,
+    tx: &broadcast::Sender<GameEvent>,
+    game: &mut Game,
+    result: GameResult,
+) {
+    game.finish(result);
+    if let Err(error) = state.db.save_result(game).await {
+        tracing::error!(?error, "Error saving game result");
+    }
+    if let Err(error) = tx.send(GameEvent::Result { result }) {
+        tracing::error!(?error, "Error sending game result");
+    }
+    if let Err(error) = state.analysis_jobs.send(game.id) {
+        tracing::error!(?error, "Error queueing analysis");
+    }
+}
+
+/// Ends the game if `mv` won it or filled the board. Returns whether the game is over.
+async fn check_game_over(
+    state: &AppState,
+    tx: &broadcast::Sender<GameEvent>,
+    game: &mut Game,
+    mv: Move,
+) -> bool {
+    if let Ok(Some(win)) = game.check_winning_move(&mv.position) {
+        game.winner = Some(win.clone());
+        let _ = tx.send(GameEvent::Winner {
+            moves: win,
+            last_move: mv,
+        });
+        finish_game(state, tx, game, GameResult::win(mv.player)).await;
+        return true;
+    }
+    if game.is_full() {
+        let _ = tx.send(GameEvent::MoveEvent { mv });
+        finish_game(state, tx, game, GameResult::DrawFullBoard).await;
+        return true;
+    }
+    false
+}
+
+/// Plays from the opening book while it has a reply, and otherwise runs the bot search on the
+/// blocking pool with the room's transposition table. The search waits for a permit so only
+/// `max_concurrent_searches` run at once, and is cancelled when the room closes.
+async fn find_bot_move(state: &AppState, room_id: &Uuid, game: &Game) -> Option<Position> {
+    if let Some(pos) = state.book.lookup(game) {
+        return Some(pos);
+    }
+    let (cancelled, mut transposition_table) = match state.rooms.lock().await.get(room_id) {
+        Some(room) => (
+            room.search_cancelled.clone(),
+            room.transposition_table.clone(),
+        ),
+        None => return None,
+    };
+    // In debug mode the reply depends only on the moves and the seed, so a game can be
+    // replayed exactly: no clock, no helper threads, and nothing left over from earlier turns.
+    if state.debug {
+        transposition_table = Arc::default();
+    }
+    let settings = BotSettings {
+        threads: state.search_threads,
+        fixed_depth: state.debug,
+        ..state.profiles.settings(game.bot_level.unwrap_or_default())
+    };
+    let mut game = game.clone();
+    let Ok(_permit) = state.search_permits.acquire().await else {
+        return None;
+    };
+    tokio::task::spawn_blocking(move || {
+        game.find_bot_move(&settings, &cancelled, &transposition_table)
+    })
+    .await
+    .unwrap_or_else(|error| {
+        tracing::error!(?error, "Bot search failed");
+        None
+    })
+}
+
+/// Suggests moves to `user_id`, who must be the side to move, and once they are found records
+/// the hint against the game type's allowance. Returns the candidates and how many hints are
+/// left.
+async fn hint(
+    state: &AppState,
+    game: &Game,
+    user_id: Uuid,
+    count: Option<usize>,
+) -> Result<(Vec<Candidate>, usize), StatusCode> {
+    let policy = state
+        .hints
+        .get(&game.game_type)
+        .copied()
+        .unwrap_or_default();
+    if policy.per_game == 0 || policy.max_candidates == 0 {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if game.result.is_some()
+        || matches!(game.status, GameStatus::Ended)
+        || game.opening_step != OpeningStep::Done
+        || game.expected_actor() != Some(user_id)
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+    let used = state
+        .db
+        .count_hints(&game.id, &user_id)
+        .await
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if used >= policy.per_game {
+        return Err(StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    let count = count
+        .unwrap_or(policy.max_candidates)
+        .clamp(1, policy.max_candidates);
+    let settings = BotSettings {
+        threads: state.search_threads,
+        ..state.profiles.settings(HINT_LEVEL)
+    };
+    let mut searched = game.clone();
+    let Ok(_permit) = state.search_permits.acquire().await else {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    };
+    let candidates = tokio::task::spawn_blocking(move || {
+        let tt = TranspositionTable::default();
+        searched.rank_moves(count, &settings, &AtomicBool::new(false), &tt)
+    })
+    .await
+    .map_err(|error| {
+        tracing::error!(?error, "Hint search failed");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Only a hint that was given is counted. The insert checks the limit again, for
+    // requests that raced this one.
+    let recorded = state
+        .db
+        .insert_hint(&game.id, &user_id, game.moves.len(), policy.per_game)
+        .await
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if !recorded {
+        return Err(StatusCode::TOO_MANY_REQUESTS);
+    }
+    let used = state
+        .db
+        .count_hints(&game.id, &user_id)
+        .await
+        .map_err(|error| {
+            tracing::error!(?error);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok((candidates, policy.per_game.saturating_sub(used)))
+}
+
+/// Analyses queued games one at a time, in the background of the bot searches.
+async fn analyse_games(state: Arc<AppState>, mut jobs: mpsc::UnboundedReceiver<Uuid>) {
+    while let Some(game_id) = jobs.recv().await {
+        if let Err(error) = analyse_game(&state, game_id).await {
+            tracing::error!(?error, %game_id, "Error analysing game");
+        }
+    }
+}
+
+async fn analyse_game(state: &AppState, game_id: Uuid) -> anyhow::Result<()> {
+    if !state.db.get_analysis(&game_id).await?.is_empty() {
+        return Ok(());
+    }
+    let game = state.db.get_game(&game_id).await?;
+    let settings = BotSettings {
+        threads: state.search_threads,
+        ..state.profiles.settings(ANALYSIS_LEVEL)
+    };
+    let _permit = state.search_permits.acquire().await?;
+    let analysis = tokio::task::spawn_blocking(move || {
+        analysis::analyse(&game, &settings, &AtomicBool::new(false))
+    })
+    .await??;
+    state.db.insert_analysis(&game_id, &analysis).await
+}
+
+/// Plays the bot's move if it is the bot's turn in a running bot game, and reports it or the
+/// result to the room.
+async fn play_bot_move(
+    state: &AppState,
+    tx: &broadcast::Sender<GameEvent>,
+    room_id: &Uuid,
+    game: &mut Game,
+) {
+    if game.result.is_some() || game.bot_player() != Some(game.next_player) {
+        return;
+    }
+    let Some(pos) = find_bot_move(state, room_id, game).await else {
+        return;
+    };
+    let bot_move = Move::new(game.next_player, pos);
+    if let Err(error) = game.play(&bot_move) {
+        tracing::error!(?error, ?bot_move, "Invalid bot move");
+        return;
+    }
+    if let Err(error) = state
+        .db
+        .insert_move(&game.id, &bot_move, game.moves.len())
+        .await
+    {
+        tracing::error!(?error, "Error inserting move");
+        return;
+    }
+    if check_game_over(state, tx, game, bot_move).await {
+        return;
+    }
+    let _ = tx.send(GameEvent::MoveEvent { mv: bot_move });
+}
+
+async fn clear_draw_offer(state: &AppState, room_id: &Uuid) {
+    if let Some(room) = state.rooms.lock().await.get_mut(room_id) {
+        room.draw_offer = None;
+    }
+}
+
+// #[tracing::instrument(skip(state, ws))]
+/// Pairs waiting players every `MATCHMAKING_INTERVAL`, so bands keep widening while nobody
+/// joins.
+async fn run_matchmaking(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(MATCHMAKING_INTERVAL);
+    loop {
+        interval.tick().await;
+        pair_players(&state).await;
+    }
+}
+
+async fn pair_players(state: &AppState) {
+    let pairs = state.matchmaking.lock().await.pair(Instant::now());
+    for (first, second) in pairs {
+        // A player may have left between pairing and now; the other keeps their place.
+        match (first.notify.is_closed(), second.notify.is_closed()) {
+            (false, false) => start_match(state, first, second).await,
+            (true, false) => state.matchmaking.lock().await.push(second),
+            (false, true) => state.matchmaking.lock().await.push(first),
+            (true, true) => {}
+        }
+    }
+}
+
+/// Creates the game for a pair, both seats taken and colours drawn at random, and sends each
+/// player the room.
+async fn start_match(state: &AppState, first: Ticket, second: Ticket) {
+    let (x, o) = if rand::random() {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    let settings = x.settings;
+    let room_id = Uuid::new_v4();
+    let mut game = Game::new(room_id, Player::X, GameType::Normal, settings.board_size);
+    game.rule_set = settings.rule_set;
+    game.set_opening(settings.opening);
+    game.set_seat(Player::X, x.user_id);
+    game.set_seat(Player::O, o.user_id);
+    if let Err(error) = state.db.new_game(&game).await {
+        // Dropping the tickets closes both sockets, and the players can queue again.
+        tracing::error!(?error, "Error creating matched game");
+        return;
+    }
+    tracing::info!(%room_id, x = %x.user_id, o = %o.user_id, "Players matched");
+    for (ticket, colour) in [(x, Player::X), (o, Player::O)] {
+        let _ = ticket.notify.send(MatchEvent::Matched {
+            room: room_id,
+            colour,
+        });
+    }
+}
+
+async fn matchmaking_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| matchmaking_websocket(socket, state))
+}
+
+/// Quick matchmaking. The client sends its token, then the `MatchSettings` it wants, and is
+/// sent `Queued` and later `Matched` with the room to join. Closing the socket leaves the
+/// queue.
+async fn matchmaking_websocket(stream: WebSocket, state: Arc<AppState>) {
+    let (mut sender, mut receiver) = stream.split();
+    let close = |reason: &'static str| {
+        Message::Close(Some(CloseFrame {
+            code: 0,
+            reason: reason.into(),
+        }))
+    };
+
+    let mut user_id = None;
+    let mut settings = None;
+    while let Some(Ok(message)) = receiver.next().await {
+        let Message::Text(text) = message else {
+            continue;
+        };
+        if user_id.is_none() {
+            let mut validation = Validation::default();
+            validation.set_audience(&["authenticated"]);
+            match decode::<Claims>(&text, state.decoding_key(), &validation) {
+                Ok(data) => user_id = Some(data.claims.sub),
+                Err(_) => {
+                    let _ = sender.send(close("Invalid token")).await;
+                    return;
                 }
             }
-            match room {
-                Some(room) => {
-                    let mut game = room.to_owned();
-                    if game.x.is_none() {
-                        game.x = Some(user_id)
-                    } else if game.o.is_none() {
-                        game.o = Some(user_id)
-                    }
-                    state.db.update_game(&game).await.map_err(|err| {
-                        tracing::error!(?err);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?;
-                    game.room_id
-                }
-                None => {
-                    let room_id: Uuid = Uuid::new_v4();
-                    let mut game = Game::new(room_id, Player::X, GameType::Normal);
-                    game.x = Some(user_id);
-                    state.db.new_game(&game).await.map_err(|err| {
-                        tracing::error!(?err);
-                        StatusCode::INTERNAL_SERVER_ERROR
-                    })?;
-                    room_id
-                }
+            continue;
+        }
+        match serde_json::from_str::<MatchSettings>(&text) {
+            Ok(requested) if (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&requested.board_size) => {
+                settings = Some(requested);
+                break;
+            }
+            _ => {
+                let _ = sender.send(close("Invalid match settings")).await;
+                return;
             }
         }
-        _ => {
-            todo!()
+    }
+    let (Some(user_id), Some(settings)) = (user_id, settings) else {
+        return;
+    };
+
+    let rating = match state.db.get_rating(&user_id).await {
+        Ok(rating) => rating.map_or(rating::DEFAULT_RATING, |player| player.rating.rating),
+        Err(error) => {
+            tracing::error!(?error, "Error reading rating");
+            let _ = sender.send(close("Matchmaking unavailable")).await;
+            return;
         }
     };
+    let (ticket, matched) = Ticket::new(user_id, rating, settings);
+    state.matchmaking.lock().await.push(ticket);
+    let queued = serde_json::to_string(&MatchEvent::Queued { rating }).unwrap();
+    if sender.send(Message::Text(queued)).await.is_err() {
+        state.matchmaking.lock().await.remove(&user_id);
+        return;
+    }
+    pair_players(&state).await;
 
-    Ok(Json(GameResponse { room: room_id }))
-    // Ok(format!("/ws/rooms/{room_id}"))
+    let mut leaving = Box::pin(async {
+        while let Some(Ok(message)) = receiver.next().await {
+            if matches!(message, Message::Close(_)) {
+                break;
+            }
+        }
+    });
+    let mut matched = matched;
+    tokio::select! {
+        event = &mut matched => match event {
+            Ok(event) => {
+                let event = serde_json::to_string(&event).unwrap();
+                let _ = sender.send(Message::Text(event)).await;
+                let _ = sender.send(close("Matched")).await;
+            }
+            // Queued again from another socket, or the game could not be created.
+            Err(_) => {
+                let _ = sender.send(close("Left the queue")).await;
+            }
+        },
+        _ = &mut leaving => {
+            // Closing the receiver marks the ticket, leaving a newer one from another socket
+            // in place.
+            drop(matched);
+            state.matchmaking.lock().await.remove_closed();
+        }
+    }
 }
 
-// #[tracing::instrument(skip(state, ws))]
 async fn websocket_handler(
     Path(room_id): Path<String>,
     ws: WebSocketUpgrade,
@@ -304,13 +1315,7 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>, room_id: String) {
     let mut tx = None;
     {
         let mut rooms = state.rooms.lock().await;
-        let room = rooms.get_mut(&room_id);
-        if room.is_none() {
-            let room = RoomState::new();
-            tx = Some(room.tx.clone());
-            rooms.insert(room_id, room);
-        } else {
-            let room = room.unwrap();
+        if let Some(room) = rooms.get_mut(&room_id) {
             if room.users.contains(&user_id) {
                 let _ = sender
                     .send(Message::Close(Some(CloseFrame {
@@ -322,6 +1327,10 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>, room_id: String) {
             }
             tx = Some(room.tx.clone());
             room.users.insert(user_id);
+        } else {
+            let room = RoomState::new();
+            tx = Some(room.tx.clone());
+            rooms.insert(room_id, room);
         }
     }
 
@@ -338,11 +1347,22 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>, room_id: String) {
     let tx = tx.unwrap();
     let mut rx = tx.subscribe();
 
+    // A bot playing X opens as soon as its opponent is in the room.
+    if let Ok(mut game) = state.db.get_active_game_for_room(&room_id).await {
+        if game.player_of(user_id).is_some() {
+            play_bot_move(&state, &tx, &room_id, &mut game).await;
+        }
+    }
+
     let sender_state = state.clone();
     let sender_tx = tx.clone();
 
     let mut send_task = tokio::spawn(async move {
         while let Ok(msg) = rx.recv().await {
+            if matches!(&msg, GameEvent::Hints { user_id: recipient, .. } if *recipient != user_id)
+            {
+                continue;
+            }
             if sender
                 .send(Message::Text(serde_json::to_string(&msg).unwrap()))
                 .await
@@ -375,6 +1395,15 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>, room_id: String) {
             let mut game = game.unwrap();
             match msg {
                 GameEvent::Message { msg, .. } => {
+                    if let Some(coordinate) = msg.strip_prefix(HIGHLIGHT_COMMAND) {
+                        match Position::parse(coordinate.trim(), game.board_size) {
+                            Ok(position) => {
+                                let _ = sender_tx.send(GameEvent::Highlight { position, user_id });
+                            }
+                            Err(_) => tracing::info!(coordinate, "Invalid highlight"),
+                        }
+                        continue;
+                    }
                     let _ = sender_tx.send(GameEvent::Message {
                         msg,
                         user: Some(User {
@@ -385,69 +1414,164 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>, room_id: String) {
                         id: Uuid::new_v4(),
                     });
                 }
-                GameEvent::MoveEvent { mv } => {
-                    if mv.player == Player::O && Some(user_id) != game.o {
+                GameEvent::OpeningMove { mv } => {
+                    if game.expected_actor() != Some(user_id)
+                        || !matches!(
+                            game.opening_step,
+                            OpeningStep::Propose | OpeningStep::Extend
+                        )
+                    {
                         continue;
                     }
-                    if mv.player == Player::X && Some(user_id) != game.x {
+                    if let Err(error) = game.play(&mv) {
+                        tracing::error!(?error, ?mv, "Invalid opening move");
                         continue;
                     }
-                    if game.play(&mv).is_ok() {
-                        if let Err(error) = sender_state
-                            .db
-                            .insert_move(&game.id, &mv, game.moves.len())
-                            .await
-                        {
-                            tracing::error!(?error, "Error inserting move");
-                        }
-                        if let Ok(Some(win)) = game.check_winning_move(&mv.position) {
-                            game.winner = Some(win);
-                            game.status = GameStatus::Ready;
-                            game.x_status = PlayerStatus::Ready;
-                            game.o_status = match game.game_type {
-                                GameType::Bot => PlayerStatus::Confirmed,
-                                GameType::Normal | GameType::Private => PlayerStatus::Ready,
-                            };
-                            if let Err(error) = sender_state.db.update_game(&game).await {
-                                tracing::error!(?error, "Error update game winner");
-                            }
-                            let _ = sender_tx.send(GameEvent::Winner {
-                                moves: game.winner.unwrap(),
-                                last_move: mv,
+                    if let Err(error) = sender_state
+                        .db
+                        .insert_move(&game.id, &mv, game.moves.len())
+                        .await
+                    {
+                        // The move was not stored, so it did not happen.
+                        tracing::error!(?error, "Error inserting move");
+                        let _ = sender_tx.send(GameEvent::InvalidMove { player: mv.player });
+                        continue;
+                    }
+                    if let Err(error) = sender_state.db.update_game(&game).await {
+                        tracing::error!(?error, "Error updating opening");
+                    }
+                    let _ = sender_tx.send(GameEvent::OpeningMove { mv });
+                    if !matches!(
+                        game.opening_step,
+                        OpeningStep::Propose | OpeningStep::Extend
+                    ) {
+                        let _ = sender_tx.send(GameEvent::Opening {
+                            step: game.opening_step,
+                        });
+                    }
+                }
+                GameEvent::ChooseColour { player } => {
+                    if game.expected_actor() != Some(user_id) {
+                        continue;
+                    }
+                    if let Err(error) = game.choose_colour(player) {
+                        tracing::error!(?error, "Invalid colour choice");
+                        continue;
+                    }
+                    if let Err(error) = sender_state.db.update_game(&game).await {
+                        tracing::error!(?error, "Error updating opening");
+                    }
+                    let _ = sender_tx.send(GameEvent::Game {
+                        game: Box::new(game),
+                    });
+                }
+                GameEvent::PlaceTwo => {
+                    if game.expected_actor() != Some(user_id) {
+                        continue;
+                    }
+                    if let Err(error) = game.extend_opening() {
+                        tracing::error!(?error, "Invalid opening extension");
+                        continue;
+                    }
+                    if let Err(error) = sender_state.db.update_game(&game).await {
+                        tracing::error!(?error, "Error updating opening");
+                    }
+                    let _ = sender_tx.send(GameEvent::Opening {
+                        step: game.opening_step,
+                    });
+                }
+                GameEvent::Hint { count } => {
+                    match hint(&sender_state, &game, user_id, count).await {
+                        Ok((candidates, remaining)) => {
+                            let _ = sender_tx.send(GameEvent::Hints {
+                                user_id,
+                                candidates,
+                                remaining,
                             });
-                            continue;
-                        }
-                        if let Err(error) = sender_tx.send(GameEvent::MoveEvent { mv }) {
-                            tracing::error!(?error, "Error sending move event");
                         }
-                        if !matches!(game.game_type, GameType::Bot) {
-                            continue;
+                        Err(status) => tracing::info!(?status, "Hint refused"),
+                    }
+                }
+                GameEvent::MoveEvent { mv } => {
+                    if game.opening_step != OpeningStep::Done
+                        || game.expected_actor() != Some(user_id)
+                    {
+                        continue;
+                    }
+                    if let Err(error) = game.play(&mv) {
+                        if let Some(reason) = error.downcast_ref::<ForbiddenMove>() {
+                            let _ = sender_tx.send(GameEvent::Forbidden {
+                                mv,
+                                reason: *reason,
+                            });
                         }
-                        let predict = game.find_bot_move(2);
-
-                        if let Some(pos) = predict {
-                            let bot_move = Move::new(Player::O, pos);
-                            game.play(&bot_move).unwrap();
-                            let _ = sender_tx.send(GameEvent::MoveEvent { mv: bot_move });
-                            let _ = sender_state
-                                .db
-                                .insert_move(&game.id, &bot_move, game.moves.len())
-                                .await;
-                            if let Ok(Some(win)) = game.check_winning_move(&pos) {
-                                game.winner = Some(win);
-                                game.status = GameStatus::Ready;
-                                game.x_status = PlayerStatus::Ready;
-                                if let Err(error) = sender_state.db.update_game(&game).await {
-                                    tracing::error!(?error, "Error update game winner");
-                                }
-                                let _ = sender_tx.send(GameEvent::Winner {
-                                    moves: game.winner.unwrap(),
-                                    last_move: bot_move,
-                                });
-                                continue;
-                            }
+                        continue;
+                    }
+                    if let Err(error) = sender_state
+                        .db
+                        .insert_move(&game.id, &mv, game.moves.len())
+                        .await
+                    {
+                        tracing::error!(?error, "Error inserting move");
+                        let _ = sender_tx.send(GameEvent::InvalidMove { player: mv.player });
+                        continue;
+                    }
+                    clear_draw_offer(&sender_state, &room_id).await;
+                    if check_game_over(&sender_state, &sender_tx, &mut game, mv).await {
+                        continue;
+                    }
+                    if let Err(error) = sender_tx.send(GameEvent::MoveEvent { mv }) {
+                        tracing::error!(?error, "Error sending move event");
+                    }
+                    play_bot_move(&sender_state, &sender_tx, &room_id, &mut game).await;
+                }
+                GameEvent::Resign => {
+                    let Some(player) = game.player_of(user_id) else {
+                        continue;
+                    };
+                    if game.result.is_some() || !matches!(game.status, GameStatus::Playing) {
+                        continue;
+                    }
+                    finish_game(
+                        &sender_state,
+                        &sender_tx,
+                        &mut game,
+                        GameResult::resign(player),
+                    )
+                    .await;
+                }
+                GameEvent::OfferDraw => {
+                    let Some(player) = game.player_of(user_id) else {
+                        continue;
+                    };
+                    if game.result.is_some() || matches!(game.game_type, GameType::Bot) {
+                        continue;
+                    }
+                    if let Some(room) = sender_state.rooms.lock().await.get_mut(&room_id) {
+                        room.draw_offer = Some(player);
+                    }
+                    let _ = sender_tx.send(GameEvent::DrawOffered { player });
+                }
+                GameEvent::AcceptDraw => {
+                    let Some(player) = game.player_of(user_id) else {
+                        continue;
+                    };
+                    let offered = match sender_state.rooms.lock().await.get_mut(&room_id) {
+                        Some(room) if room.draw_offer == Some(player.opponent()) => {
+                            room.draw_offer.take().is_some()
                         }
+                        _ => false,
+                    };
+                    if !offered || game.result.is_some() {
+                        continue;
                     }
+                    finish_game(
+                        &sender_state,
+                        &sender_tx,
+                        &mut game,
+                        GameResult::DrawAgreement,
+                    )
+                    .await;
                 }
                 GameEvent::PlayAgain => {
                     if game.o != Some(user_id) && game.x != Some(user_id) {
@@ -455,30 +1579,29 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>, room_id: String) {
                     }
                     match game.game_type {
                         GameType::Bot => {
-                            if game.x != Some(user_id) {
+                            if game.player_of(user_id) == game.bot_player() {
                                 continue;
                             }
                             game.status = GameStatus::Ended;
                             let _ = sender_state.db.update_game(&game).await;
                             let next_player = game.next_player;
-                            let mut game = Game::new(room_id, next_player, GameType::Bot);
-                            game.x = Some(user_id);
-                            game.o = Some(Uuid::nil());
+                            let rule_set = game.rule_set;
+                            let bot_level = game.bot_level;
+                            // The next seed follows from the last, so a seeded session
+                            // replays the same way game after game.
+                            let seed = game.seed.wrapping_add(1);
+                            let (x_player, o_player) = (game.x, game.o);
+                            let mut game =
+                                Game::new(room_id, next_player, GameType::Bot, game.board_size);
+                            game.rule_set = rule_set;
+                            game.bot_level = bot_level;
+                            game.seed = seed;
+                            game.x = x_player;
+                            game.o = o_player;
                             if let Err(error) = sender_state.db.new_game(&game).await {
                                 tracing::error!(?error, "Error saving game");
                             }
-                            if game.next_player == Player::O {
-                                let predict = game.find_bot_move(2);
-                                if let Some(pos) = predict {
-                                    let bot_move = Move::new(Player::O, pos);
-                                    game.play(&bot_move).unwrap();
-                                    let _ = sender_tx.send(GameEvent::MoveEvent { mv: bot_move });
-                                    let _ = sender_state
-                                        .db
-                                        .insert_move(&game.id, &bot_move, game.moves.len())
-                                        .await;
-                                }
-                            }
+                            play_bot_move(&sender_state, &sender_tx, &room_id, &mut game).await;
                             let _ = sender_tx.send(GameEvent::Game {
                                 game: Box::new(game),
                             });
@@ -501,7 +1624,16 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>, room_id: String) {
                                 }
                                 let x_player = game.x;
                                 let o_player = game.o;
-                                let mut game = Game::new(room_id, game.next_player, game.game_type);
+                                let rule_set = game.rule_set;
+                                let opening = game.opening;
+                                let mut game = Game::new(
+                                    room_id,
+                                    game.next_player,
+                                    game.game_type,
+                                    game.board_size,
+                                );
+                                game.rule_set = rule_set;
+                                game.set_opening(opening);
                                 game.x = x_player;
                                 game.o = o_player;
                                 game.status = GameStatus::Playing;
@@ -529,8 +1661,9 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>, room_id: String) {
 
     {
         let game = state.db.get_active_game_for_room(&room_id).await;
-        if game.is_ok() {
-            let mut game = game.unwrap();
+        if let Ok(mut game) = game {
+            // A player who leaves a game under way loses on time unless they come back.
+            let abandoned = game.player_of(user_id).filter(|_| is_under_way(&game));
             {
                 let mut rooms = state.rooms.lock().await;
 
@@ -573,22 +1706,39 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>, room_id: String) {
                     }
                 }
             }
-            if matches!(
-                game.x_status,
-                PlayerStatus::Left | PlayerStatus::ConfirmedThenLeft
-            ) && (matches!(
-                game.o_status,
-                PlayerStatus::Left | PlayerStatus::ConfirmedThenLeft
-            ) || matches!(game.game_type, GameType::Bot))
-            {
-                tracing::info!("Game ended");
-                game.status = GameStatus::Ended;
-                if let Err(error) = state.db.update_game(&game).await {
-                    tracing::error!(?error, "Error updating game status");
+            let bot = game.bot_player();
+            let seat_empty = |player: Player, status: &PlayerStatus| {
+                bot == Some(player)
+                    || matches!(status, PlayerStatus::Left | PlayerStatus::ConfirmedThenLeft)
+            };
+            let seats_empty =
+                seat_empty(Player::X, &game.x_status) && seat_empty(Player::O, &game.o_status);
+            match abandoned {
+                // The bot keeps the game open for the player to come back to, but only the
+                // player's moves wake it, so the room can go.
+                Some(leaver) if seats_empty && bot.is_some() => {
+                    if let Some(room) = state.rooms.lock().await.remove(&room_id) {
+                        room.search_cancelled.store(true, Ordering::Relaxed);
+                    }
+                    tokio::spawn(forfeit_after_grace(state.clone(), room_id, game.id, leaver));
                 }
-                {
-                    let mut rooms = state.rooms.lock().await;
-                    if let Some(room) = rooms.remove(&room_id) {
+                _ if seats_empty => {
+                    tracing::info!("Game ended");
+                    let room = state.rooms.lock().await.remove(&room_id);
+                    if let Some(room) = &room {
+                        room.search_cancelled.store(true, Ordering::Relaxed);
+                        if let Some(leaver) = abandoned {
+                            // The other player left first and loses, whoever is on move.
+                            let loser = leaver.opponent();
+                            finish_game(&state, &room.tx, &mut game, GameResult::time_out(loser))
+                                .await;
+                        }
+                    }
+                    game.status = GameStatus::Ended;
+                    if let Err(error) = state.db.update_game(&game).await {
+                        tracing::error!(?error, "Error updating game status");
+                    }
+                    if let Some(room) = room {
                         if let Err(error) = room.tx.send(GameEvent::Status {
                             status: GameStatus::Ended,
                         }) {
@@ -596,7 +1746,54 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>, room_id: String) {
                         }
                     }
                 }
+                Some(leaver) => {
+                    tokio::spawn(forfeit_after_grace(state.clone(), room_id, game.id, leaver));
+                }
+                None => {}
             }
         }
     }
 }
+
+/// Whether a game has moves on the board and no result yet, so leaving it loses.
+fn is_under_way(game: &Game) -> bool {
+    matches!(game.status, GameStatus::Playing) && game.result.is_none() && !game.moves.is_empty()
+}
+
+/// Records a loss on time for `leaver` if they have not come back to the room within the
+/// grace period.
+async fn forfeit_after_grace(state: Arc<AppState>, room_id: Uuid, game_id: Uuid, leaver: Player) {
+    tokio::time::sleep(state.abandon_grace).await;
+    let Ok(mut game) = state.db.get_active_game_for_room(&room_id).await else {
+        return;
+    };
+    let status = match leaver {
+        Player::X => game.x_status.clone(),
+        Player::O => game.o_status.clone(),
+    };
+    let still_gone = matches!(status, PlayerStatus::Left | PlayerStatus::ConfirmedThenLeft);
+    if game.id != game_id || !still_gone || !is_under_way(&game) {
+        return;
+    }
+    let tx = state
+        .rooms
+        .lock()
+        .await
+        .get(&room_id)
+        .map(|room| room.tx.clone());
+    // A bot game's room closes when the player leaves; with nobody to tell, the game ends.
+    let closed = tx.is_none();
+    let tx = tx.unwrap_or_else(|| broadcast::channel(1).0);
+    finish_game(&state, &tx, &mut game, GameResult::time_out(leaver)).await;
+    // The result readies both seats for a rematch; the leaver is still away.
+    match leaver {
+        Player::X => game.x_status = status,
+        Player::O => game.o_status = status,
+    }
+    if closed {
+        game.status = GameStatus::Ended;
+    }
+    if let Err(error) = state.db.update_game(&game).await {
+        tracing::error!(?error, "Error updating game status");
+    }
+}
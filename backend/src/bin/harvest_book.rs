@@ -0,0 +1,45 @@
+//! Adds the winners' moves from finished games to an opening book file.
+//!
+//! Usage: `harvest_book <book.json> [min_wins]`, with `BACKEND_DATABASE_URL` set. Only
+//! games won by players with at least `min_wins` wins (default 1) are used, and games already
+//! in the book are skipped, so running it again only adds new games. The file is created if
+//! it does not exist.
+
+use backend::book::OpeningBook;
+use backend::db::Db;
+use sqlx::PgPool;
+use std::path::Path;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("usage: harvest_book <book.json> [min_wins]"))?;
+    let min_wins = match args.next() {
+        Some(min_wins) => min_wins.parse()?,
+        None => 1,
+    };
+    let database_url = std::env::var("BACKEND_DATABASE_URL")?;
+
+    let mut book = if Path::new(&path).exists() {
+        OpeningBook::load(&path)?
+    } else {
+        OpeningBook::default()
+    };
+    let db = Db::new(PgPool::connect(&database_url).await?);
+    let games = db.get_won_games(min_wins).await?;
+    let mut harvested = 0;
+    for game in &games {
+        if book.harvest(game)? {
+            harvested += 1;
+        }
+    }
+    book.save(&path)?;
+    println!(
+        "Harvested {harvested} of {} games, {} positions booked",
+        games.len(),
+        book.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,35 @@
+//! Gomocup / Piskvork engine speaking the text protocol on stdin and stdout.
+//!
+//! Usage: `pbrain-gomoku [beginner|casual|strong|expert] [profile.toml]`, default `expert`
+//! with the built-in evaluation profile. Tournament managers pick engines up by the
+//! `pbrain-` prefix.
+
+use backend::gomocup::{Engine, Reply};
+use backend::models::BotLevel;
+use backend::profile::EvalProfile;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let level = match args.next() {
+        Some(level) => serde_json::from_value(serde_json::Value::String(level))?,
+        None => BotLevel::Expert,
+    };
+    let mut engine = Engine::new(level);
+    if let Some(path) = args.next() {
+        engine.set_profile(Arc::new(EvalProfile::load(path)?));
+    }
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        match engine.handle(&line?) {
+            Reply::Line(reply) => {
+                writeln!(stdout, "{reply}")?;
+                stdout.flush()?;
+            }
+            Reply::Silent => {}
+            Reply::End => break,
+        }
+    }
+    Ok(())
+}
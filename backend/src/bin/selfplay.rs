@@ -0,0 +1,57 @@
+//! Plays two bot configurations against each other and reports how they compare.
+//!
+//! Usage: `selfplay <a> <b> [--games N] [--size N] [--rule RULE] [--opening-plies N]
+//! [--jobs N] [--seed N] [--save games.json]`, where `a` and `b` are written like
+//! `strong:depth=6,time=200` (see `EngineConfig`). Results are from `a`'s point of view.
+
+use anyhow::{anyhow, Result};
+use backend::models::{RuleSet, DEFAULT_BOARD_SIZE};
+use backend::tournament::{self, EngineConfig, TournamentOptions};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+const USAGE: &str = "usage: selfplay <a> <b> [--games N] [--size N] [--rule RULE] \
+                     [--opening-plies N] [--jobs N] [--seed N] [--save FILE]";
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let a: EngineConfig = args.next().ok_or_else(|| anyhow!(USAGE))?.parse()?;
+    let b: EngineConfig = args.next().ok_or_else(|| anyhow!(USAGE))?.parse()?;
+    let mut options = TournamentOptions {
+        games: 20,
+        board_size: DEFAULT_BOARD_SIZE,
+        rule_set: RuleSet::default(),
+        opening_plies: 2,
+        jobs: 1,
+    };
+    let mut seed = None;
+    let mut save = None;
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| anyhow!(USAGE))?;
+        match flag.as_str() {
+            "--games" => options.games = value.parse()?,
+            "--size" => options.board_size = value.parse()?,
+            "--rule" => {
+                options.rule_set = serde_json::from_value(serde_json::Value::String(value))?
+            }
+            "--opening-plies" => options.opening_plies = value.parse()?,
+            "--jobs" => options.jobs = value.parse()?,
+            "--seed" => seed = Some(value.parse()?),
+            "--save" => save = Some(value),
+            _ => return Err(anyhow!(USAGE)),
+        }
+    }
+
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    println!("A: {}\nB: {}", a.name, b.name);
+    let report = tournament::run(&a, &b, &options, &mut rng);
+    println!("{report}");
+    if let Some(path) = save {
+        std::fs::write(&path, serde_json::to_string_pretty(&report.games)?)?;
+        println!("Saved {} games to {path}", report.games.len());
+    }
+    Ok(())
+}
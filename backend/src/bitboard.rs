@@ -0,0 +1,356 @@
+use crate::models::{Player, Position};
+use std::sync::Arc;
+
+/// How far along a diagonal, either side of the stone, `winning_threat` looks.
+const DIAGONAL_REACH: usize = 5;
+
+/// A pattern string turned into bit masks, bit `i` standing for character `i`.
+#[derive(Debug)]
+pub struct Pattern {
+    len: usize,
+    x: u32,
+    o: u32,
+    empty: Vec<usize>,
+    /// Who the pattern is a threat for: whoever has more stones in it.
+    pub player: Player,
+    /// How many stones that player has in it.
+    pub stones: usize,
+    /// What finding it is worth to its player before weighting.
+    pub score: i32,
+    /// How many threats a match counts as.
+    pub count: usize,
+}
+
+impl Pattern {
+    /// Reads a pattern: `o`/`x` are stones and anything else an empty cell, left to right
+    /// along a line.
+    pub fn compile(pattern: &str, score: i32) -> Self {
+        let mut x = 0u32;
+        let mut o = 0u32;
+        let mut empty = vec![];
+        for (i, c) in pattern.chars().enumerate() {
+            match c {
+                'x' => x |= 1 << i,
+                'o' => o |= 1 << i,
+                _ => empty.push(i),
+            }
+        }
+        let (xs, os) = (x.count_ones() as usize, o.count_ones() as usize);
+        Self {
+            len: pattern.len(),
+            x,
+            o,
+            empty,
+            player: if os > xs { Player::O } else { Player::X },
+            stones: os.max(xs),
+            score,
+            count: 1,
+        }
+    }
+
+    fn matches_at(&self, line: &Line, offset: usize) -> bool {
+        let mask = (1 << self.len) - 1;
+        offset + self.len <= line.len
+            && (line.x >> offset) & mask == self.x
+            && (line.o >> offset) & mask == self.o
+    }
+
+    /// Leftmost offset in `from..to` where the whole pattern fits and matches.
+    fn find(&self, line: &Line, from: usize, to: usize) -> Option<usize> {
+        (from..to.min(line.len)).find(|&offset| self.matches_at(line, offset))
+    }
+
+    /// Offsets of the empty cells of the pattern.
+    pub fn empty_cells(&self) -> &[usize] {
+        &self.empty
+    }
+}
+
+/// The stones on one row, column or diagonal.
+#[derive(Debug, Clone, Copy, Default)]
+struct Line {
+    x: u32,
+    o: u32,
+    len: usize,
+}
+
+impl Line {
+    fn stones(&self, player: Player) -> (u32, u32) {
+        match player {
+            Player::X => (self.x, self.o),
+            Player::O => (self.o, self.x),
+        }
+    }
+}
+
+/// The board as one pair of bit masks per line. Rows come first, then columns, then the
+/// down-right diagonals and finally the up-right diagonals. Bits run left to right, so
+/// along the columns they run top to bottom.
+///
+/// First matches of the evaluation patterns are cached per line and only recomputed for
+/// lines a stone has been placed on or removed from since the last `refresh`, or for every
+/// line when the patterns change.
+#[derive(Debug, Clone, Default)]
+pub struct BitBoard {
+    size: usize,
+    lines: Vec<Line>,
+    patterns: Arc<Vec<Pattern>>,
+    threats: Vec<Vec<Option<usize>>>,
+    dirty: Vec<usize>,
+    is_dirty: Vec<bool>,
+}
+
+impl BitBoard {
+    pub fn new(size: usize) -> Self {
+        let diagonals = 2 * size - 1;
+        let mut lines = vec![];
+        lines.extend((0..2 * size).map(|_| Line {
+            len: size,
+            ..Default::default()
+        }));
+        for _ in 0..2 {
+            lines.extend((0..diagonals).map(|i| Line {
+                len: size - i.abs_diff(size - 1),
+                ..Default::default()
+            }));
+        }
+        let count = lines.len();
+        Self {
+            size,
+            lines,
+            patterns: Arc::default(),
+            threats: vec![vec![]; count],
+            dirty: (0..count).collect(),
+            is_dirty: vec![true; count],
+        }
+    }
+
+    /// The line and bit of `pos` along each of `DIRECTIONS`: the row, the column, the
+    /// down-right diagonal and the up-right diagonal.
+    pub fn lines_through(&self, pos: &Position) -> [(usize, usize); 4] {
+        let n = self.size;
+        let (row, col) = (pos.row, pos.col);
+        let diagonal = col + n - 1 - row;
+        let anti_diagonal = row + col;
+        [
+            (row, col),
+            (n + col, row),
+            (2 * n + diagonal, col.min(row)),
+            (
+                4 * n - 1 + anti_diagonal,
+                col - anti_diagonal.saturating_sub(n - 1),
+            ),
+        ]
+    }
+
+    /// The cell at `bit` along `line`.
+    pub fn position(&self, line: usize, bit: usize) -> Position {
+        let n = self.size;
+        if line < n {
+            Position::new(bit, line)
+        } else if line < 2 * n {
+            Position::new(line - n, bit)
+        } else if line < 4 * n - 1 {
+            let diagonal = line - 2 * n;
+            let (row, col) = (
+                (n - 1).saturating_sub(diagonal),
+                diagonal.saturating_sub(n - 1),
+            );
+            Position::new(col + bit, row + bit)
+        } else {
+            let anti_diagonal = line - (4 * n - 1);
+            let col = anti_diagonal.saturating_sub(n - 1) + bit;
+            Position::new(col, anti_diagonal - col)
+        }
+    }
+
+    /// Lines in the order the evaluation has always scanned them, leaving out diagonals too
+    /// short to hold five.
+    pub fn scan_order(&self) -> impl Iterator<Item = usize> + '_ {
+        let n = self.size;
+        let rows_and_columns = 0..2 * n;
+        // Down-right diagonals starting on the left edge, top to bottom, then those starting
+        // on the top edge, left to right.
+        let left_edge = (0..n).map(move |row| 2 * n + (n - 1 - row));
+        let top_edge = (1..n).map(move |col| 2 * n + (n - 1 + col));
+        // Up-right diagonals starting on the bottom edge, left to right, then those starting
+        // on the left edge, bottom to top.
+        let bottom_edge = (0..n).map(move |col| 4 * n - 1 + (n - 1 + col));
+        let left_edge_up = (0..n - 1).rev().map(move |row| 4 * n - 1 + row);
+        rows_and_columns
+            .chain(left_edge)
+            .chain(top_edge)
+            .chain(bottom_edge)
+            .chain(left_edge_up)
+            .filter(|&line| self.lines[line].len >= 5)
+    }
+
+    pub fn place(&mut self, pos: &Position, player: Player) {
+        for (line, bit) in self.lines_through(pos) {
+            match player {
+                Player::X => self.lines[line].x |= 1 << bit,
+                Player::O => self.lines[line].o |= 1 << bit,
+            }
+            self.mark_dirty(line);
+        }
+    }
+
+    pub fn remove(&mut self, pos: &Position) {
+        for (line, bit) in self.lines_through(pos) {
+            self.lines[line].x &= !(1 << bit);
+            self.lines[line].o &= !(1 << bit);
+            self.mark_dirty(line);
+        }
+    }
+
+    fn mark_dirty(&mut self, line: usize) {
+        if !self.is_dirty[line] {
+            self.is_dirty[line] = true;
+            self.dirty.push(line);
+        }
+    }
+
+    /// Recomputes the cached matches of `patterns` on lines changed since the last refresh,
+    /// or on every line when `patterns` are not the ones cached.
+    pub fn refresh(&mut self, patterns: &Arc<Vec<Pattern>>) {
+        if !Arc::ptr_eq(&self.patterns, patterns) {
+            self.patterns = patterns.clone();
+            self.dirty = (0..self.lines.len()).collect();
+            self.is_dirty.fill(true);
+        }
+        for line in std::mem::take(&mut self.dirty) {
+            self.is_dirty[line] = false;
+            let stones = self.lines[line];
+            self.threats[line] = self
+                .patterns
+                .iter()
+                .map(|pattern| pattern.find(&stones, 0, stones.len))
+                .collect();
+        }
+    }
+
+    /// Each pattern found on `line`, with the offset of its leftmost match. Only up to date
+    /// after `refresh`.
+    pub fn threats(&self, line: usize) -> impl Iterator<Item = (&Pattern, usize)> + '_ {
+        self.threats[line]
+            .iter()
+            .enumerate()
+            .filter_map(|(i, offset)| offset.map(|offset| (&self.patterns[i], offset)))
+    }
+
+    /// Whether `line` has a run of `player` stones that `is_five` accepts. `is_five` gets the
+    /// run length and whether both ends are capped by opponent stones.
+    pub fn has_five(
+        &self,
+        line: usize,
+        player: Player,
+        is_five: impl Fn(usize, bool) -> bool,
+    ) -> bool {
+        let stones = self.lines[line];
+        let (mut own, opponent) = stones.stones(player);
+        while own != 0 {
+            let start = own.trailing_zeros() as usize;
+            let len = (own >> start).trailing_ones() as usize;
+            let end = start + len;
+            let blocked = start > 0
+                && opponent & (1 << (start - 1)) != 0
+                && end < stones.len
+                && opponent & (1 << end) != 0;
+            if is_five(len, blocked) {
+                return true;
+            }
+            own &= !(((1 << len) - 1) << start);
+        }
+        false
+    }
+
+    /// The first and last bit of the run of `player` stones through `bit` on `line`, and
+    /// whether both ends are capped by opponent stones.
+    pub fn run_through(&self, line: usize, bit: usize, player: Player) -> (usize, usize, bool) {
+        let stones = self.lines[line];
+        let (own, opponent) = stones.stones(player);
+        let below = if bit == 0 {
+            0
+        } else {
+            (own << (32 - bit)).leading_ones() as usize
+        };
+        let above = (own >> (bit + 1)).trailing_ones() as usize;
+        let (start, end) = (bit - below, bit + above);
+        let blocked = start > 0
+            && opponent & (1 << (start - 1)) != 0
+            && end + 1 < stones.len
+            && opponent & (1 << (end + 1)) != 0;
+        (start, end, blocked)
+    }
+
+    /// Number of cells on `line`.
+    pub fn line_len(&self, line: usize) -> usize {
+        self.lines[line].len
+    }
+
+    /// Empty cells in any five-cell window that holds exactly `stones` stones of `player` and
+    /// none of the opponent's, in scan order and without duplicates. With `stones` at 4 these
+    /// are the cells that may complete a five, at 3 those that may make a four.
+    pub fn window_cells(&self, player: Player, stones: u32) -> Vec<Position> {
+        let mut seen = vec![false; self.size * self.size];
+        let mut cells = vec![];
+        let window = (1u32 << 5) - 1;
+        for line in self.scan_order() {
+            let stones_on_line = self.lines[line];
+            let (own, opponent) = stones_on_line.stones(player);
+            for offset in 0..=stones_on_line.len - 5 {
+                let (own, opponent) = ((own >> offset) & window, (opponent >> offset) & window);
+                if opponent != 0 || own.count_ones() != stones {
+                    continue;
+                }
+                let mut empty = !own & window;
+                while empty != 0 {
+                    let bit = empty.trailing_zeros() as usize;
+                    empty &= empty - 1;
+                    let pos = self.position(line, offset + bit);
+                    if !std::mem::replace(&mut seen[pos.row * self.size + pos.col], true) {
+                        cells.push(pos);
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    /// The most stones in any of `patterns` for `player` through `pos`, if such shapes lie on
+    /// at least two of its lines, and 0 otherwise. Rows and columns are searched in full,
+    /// diagonals only within `DIAGONAL_REACH` of `pos`.
+    pub fn winning_threat(&self, pos: &Position, player: Player, patterns: &[Pattern]) -> usize {
+        let mut lines = 0;
+        let mut best = 0;
+        for (i, (line, bit)) in self.lines_through(pos).into_iter().enumerate() {
+            let stones = self.lines[line];
+            let (from, to) = if i < 2 {
+                (0, stones.len)
+            } else {
+                (bit.saturating_sub(DIAGONAL_REACH), bit + DIAGONAL_REACH + 1)
+            };
+            // A pattern longer than the stretch searched cannot fit in it.
+            let count = patterns
+                .iter()
+                .filter(|pattern| pattern.player == player)
+                .filter(|pattern| {
+                    (to + 1)
+                        .checked_sub(pattern.len)
+                        .is_some_and(|last| pattern.find(&stones, from, last).is_some())
+                })
+                .map(|pattern| pattern.stones)
+                .max()
+                .unwrap_or(0);
+            if count > 0 {
+                lines += 1;
+                best = best.max(count);
+            }
+        }
+        if lines >= 2 {
+            best
+        } else {
+            0
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use crate::models::{Game, Move, Player, Position, RuleSet, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use anyhow::{anyhow, Result};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use uuid::Uuid;
+
+/// How many plies the bot plays from the book when the file does not say.
+pub const DEFAULT_BOOK_PLIES: usize = 10;
+
+/// The eight symmetries of a square board, as a transpose followed by optional flips.
+const SYMMETRIES: usize = 8;
+const FLIP_COLUMNS: usize = 1;
+const FLIP_ROWS: usize = 2;
+const TRANSPOSE: usize = 4;
+
+/// A game line as stored in a book file. Every prefix of `moves` books the move that
+/// follows it, or only the moves of `winner` when it is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookLine {
+    pub board_size: usize,
+    #[serde(default)]
+    pub rule_set: RuleSet,
+    /// Who played the first move, black under Renju rules.
+    #[serde(default = "default_init_player")]
+    pub init_player: Player,
+    pub moves: Vec<Position>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub winner: Option<Player>,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// The game the line was harvested from, so it is only booked once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_id: Option<Uuid>,
+}
+
+fn default_init_player() -> Player {
+    Player::X
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BookFile {
+    #[serde(default = "default_plies")]
+    max_plies: usize,
+    lines: Vec<BookLine>,
+}
+
+fn default_plies() -> usize {
+    DEFAULT_BOOK_PLIES
+}
+
+/// Stones relative to the side to move, as `(row * size + col) * 2 + 1` for the opponent's
+/// stones and without the `+ 1` for the mover's, sorted. Who moved first tells which side is
+/// black, which matters for rule sets that restrict black.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BookKey {
+    board_size: usize,
+    rule_set: RuleSet,
+    init_player: Player,
+    stones: Vec<u16>,
+}
+
+/// Replies for the first plies of a game, keyed by position up to rotation and reflection.
+#[derive(Debug)]
+pub struct OpeningBook {
+    pub max_plies: usize,
+    lines: Vec<BookLine>,
+    replies: HashMap<BookKey, Vec<(Position, u32)>>,
+    /// Games whose lines are in the book.
+    games: HashSet<Uuid>,
+}
+
+impl Default for OpeningBook {
+    fn default() -> Self {
+        Self::new(DEFAULT_BOOK_PLIES)
+    }
+}
+
+impl OpeningBook {
+    pub fn new(max_plies: usize) -> Self {
+        Self {
+            max_plies,
+            lines: vec![],
+            replies: HashMap::new(),
+            games: HashSet::new(),
+        }
+    }
+
+    /// Reads a JSON book file: `{ "max_plies": 10, "lines": [{ "board_size": 15,
+    /// "rule_set": "freestyle", "moves": [{ "col": 7, "row": 7 }, ...] }] }`. Fails on the
+    /// first line `add_line` refuses.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file: BookFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let mut book = Self::new(file.max_plies);
+        for (index, line) in file.lines.into_iter().enumerate() {
+            book.add_line(line)
+                .map_err(|error| anyhow!("line {}: {error}", index + 1))?;
+        }
+        Ok(book)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let file = BookFile {
+            max_plies: self.max_plies,
+            lines: self.lines.clone(),
+        };
+        std::fs::write(path, serde_json::to_string_pretty(&file)?)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.replies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replies.is_empty()
+    }
+
+    /// Books a line. Fails, booking nothing, if the board size is unsupported or a move is
+    /// off the board or on a taken cell.
+    pub fn add_line(&mut self, line: BookLine) -> Result<()> {
+        if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&line.board_size) {
+            return Err(anyhow!("unsupported board size {}", line.board_size));
+        }
+        let mut taken = HashSet::new();
+        for pos in &line.moves {
+            if pos.col >= line.board_size || pos.row >= line.board_size {
+                return Err(anyhow!("{pos:?} is off the board"));
+            }
+            if !taken.insert(*pos) {
+                return Err(anyhow!("{pos:?} is played twice"));
+            }
+        }
+        let moves: Vec<Move> = line
+            .moves
+            .iter()
+            .enumerate()
+            .map(|(i, pos)| {
+                // Lines in files only list positions; the players alternate from the first.
+                let player = if i % 2 == 0 {
+                    line.init_player
+                } else {
+                    line.init_player.opponent()
+                };
+                Move::new(player, *pos)
+            })
+            .collect();
+        self.add_moves(
+            line.board_size,
+            line.rule_set,
+            line.init_player,
+            &moves,
+            line.winner,
+            line.weight,
+        );
+        self.games.extend(line.game_id);
+        self.lines.push(line);
+        Ok(())
+    }
+
+    /// Books the winner's moves from a finished game. Returns whether it did: games without
+    /// a winner, and games already in the book, are skipped.
+    pub fn harvest(&mut self, game: &Game) -> Result<bool> {
+        let Some(winner) = game.result.and_then(|result| result.winner()) else {
+            return Ok(false);
+        };
+        if self.games.contains(&game.id) {
+            return Ok(false);
+        }
+        self.add_line(BookLine {
+            board_size: game.board_size,
+            rule_set: game.rule_set,
+            init_player: game.moves.first().map_or(game.init_player, |mv| mv.player),
+            moves: game
+                .moves
+                .iter()
+                .take(self.max_plies)
+                .map(|mv| mv.position)
+                .collect(),
+            winner: Some(winner),
+            weight: 1,
+            game_id: Some(game.id),
+        })?;
+        Ok(true)
+    }
+
+    fn add_moves(
+        &mut self,
+        board_size: usize,
+        rule_set: RuleSet,
+        init_player: Player,
+        moves: &[Move],
+        winner: Option<Player>,
+        weight: u32,
+    ) {
+        for (ply, reply) in moves.iter().enumerate().take(self.max_plies) {
+            if winner.is_some_and(|winner| winner != reply.player) {
+                continue;
+            }
+            let (key, symmetry) = normalise(
+                board_size,
+                rule_set,
+                init_player,
+                &moves[..ply],
+                reply.player,
+            );
+            let reply = transform(symmetry, &reply.position, board_size);
+            let replies = self.replies.entry(key).or_default();
+            match replies.iter_mut().find(|(pos, _)| *pos == reply) {
+                Some((_, count)) => *count += weight,
+                None => replies.push((reply, weight)),
+            }
+        }
+    }
+
+    /// A booked reply for the side to move, picked by weight among the legal ones with the
+    /// game's bot RNG.
+    pub fn lookup(&self, game: &Game) -> Option<Position> {
+        if game.moves.len() >= self.max_plies {
+            return None;
+        }
+        let init_player = game.moves.first().map_or(game.init_player, |mv| mv.player);
+        let (key, symmetry) = normalise(
+            game.board_size,
+            game.rule_set,
+            init_player,
+            &game.moves,
+            game.next_player,
+        );
+        let (board_size, mover) = (game.board_size, game.next_player);
+        let mut rng = game.bot_rng();
+        let mut game = game.clone();
+        let replies: Vec<(Position, u32)> = self
+            .replies
+            .get(&key)?
+            .iter()
+            .map(|(pos, weight)| (untransform(symmetry, pos, board_size), *weight))
+            .filter(|(pos, _)| game.board[pos.row][pos.col].is_none() && game.is_legal(pos, mover))
+            .collect();
+        let total: u32 = replies.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rng.gen_range(0..total);
+        for (pos, weight) in replies {
+            if pick < weight {
+                return Some(pos);
+            }
+            pick -= weight;
+        }
+        None
+    }
+}
+
+/// The smallest key over all symmetries of the position, and the symmetry that gives it.
+fn normalise(
+    board_size: usize,
+    rule_set: RuleSet,
+    init_player: Player,
+    moves: &[Move],
+    mover: Player,
+) -> (BookKey, usize) {
+    (0..SYMMETRIES)
+        .map(|symmetry| {
+            let mut stones: Vec<u16> = moves
+                .iter()
+                .map(|mv| {
+                    let pos = transform(symmetry, &mv.position, board_size);
+                    let cell = (pos.row * board_size + pos.col) as u16;
+                    cell * 2 + u16::from(mv.player != mover)
+                })
+                .collect();
+            stones.sort_unstable();
+            (stones, symmetry)
+        })
+        .min()
+        .map(|(stones, symmetry)| {
+            (
+                BookKey {
+                    board_size,
+                    rule_set,
+                    init_player,
+                    stones,
+                },
+                symmetry,
+            )
+        })
+        .unwrap()
+}
+
+fn transform(symmetry: usize, pos: &Position, board_size: usize) -> Position {
+    let (mut col, mut row) = if symmetry & TRANSPOSE != 0 {
+        (pos.row, pos.col)
+    } else {
+        (pos.col, pos.row)
+    };
+    if symmetry & FLIP_COLUMNS != 0 {
+        col = board_size - 1 - col;
+    }
+    if symmetry & FLIP_ROWS != 0 {
+        row = board_size - 1 - row;
+    }
+    Position::new(col, row)
+}
+
+fn untransform(symmetry: usize, pos: &Position, board_size: usize) -> Position {
+    let mut pos = *pos;
+    if symmetry & FLIP_COLUMNS != 0 {
+        pos.col = board_size - 1 - pos.col;
+    }
+    if symmetry & FLIP_ROWS != 0 {
+        pos.row = board_size - 1 - pos.row;
+    }
+    if symmetry & TRANSPOSE != 0 {
+        pos = Position::new(pos.row, pos.col);
+    }
+    pos
+}
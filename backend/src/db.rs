@@ -1,6 +1,12 @@
-use crate::models::{Game, GameDb, GameStatus, GameType, Move, Player, PlayerStatus};
-use anyhow::Result;
-use sqlx::PgPool;
+use crate::analysis::{MoveAnalysis, MoveClass};
+use crate::models::{
+    BotLevel, Game, GameDb, GameResult, GameStatus, GameSummary, GameType, Move, Opening,
+    OpeningStep, Player, PlayerStatus, Position, RuleSet,
+};
+use crate::rating::{self, PlayerRating, Rating, RatingChange};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
 use uuid::Uuid;
 
 #[derive(Clone)]
@@ -16,53 +22,347 @@ impl Db {
     #[tracing::instrument(skip(self))]
     pub async fn new_game(&self, game: &Game) -> Result<()> {
         sqlx::query!(
-            "INSERT INTO game (id, room_id, x, o, init_player, game_type, status) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            "INSERT INTO game (id, room_id, x, o, init_player, game_type, status, board_size, rule_set, opening, opening_step, bot_level, seed, invite_code) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
             game.id,
             game.room_id,
             game.x,
             game.o,
             game.next_player as _,
             game.game_type as _,
-            game.status as _
+            game.status as _,
+            game.board_size as i16,
+            game.rule_set as _,
+            game.opening as _,
+            game.opening_step as _,
+            game.bot_level as _,
+            game.seed,
+            game.invite_code
         )
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
+    /// Seats `user_id` in the empty seat of the unfinished private game with `invite_code`.
+    /// Returns the room, or `None` if there is no such game or both seats are taken. A player
+    /// already seated gets the room back.
     #[tracing::instrument(skip(self))]
-    pub async fn update_game(&self, game: &Game) -> Result<()> {
+    pub async fn join_private_game(
+        &self,
+        invite_code: &str,
+        user_id: &Uuid,
+    ) -> Result<Option<Uuid>> {
+        let room_id = sqlx::query_scalar!(
+            r#"
+            UPDATE game SET
+                x = CASE WHEN x IS NULL THEN $2 ELSE x END,
+                o = CASE WHEN x IS NOT NULL AND o IS NULL THEN $2 ELSE o END
+            WHERE invite_code = $1 AND status != 'ended'
+            AND (x IS NULL OR o IS NULL)
+            AND x IS DISTINCT FROM $2 AND o IS DISTINCT FROM $2
+            RETURNING room_id
+            "#,
+            invite_code,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        if room_id.is_some() {
+            return Ok(room_id);
+        }
+        let room_id = sqlx::query_scalar!(
+            r#"
+            SELECT room_id FROM game
+            WHERE invite_code = $1 AND status != 'ended' AND (x = $2 OR o = $2)
+            "#,
+            invite_code,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(room_id)
+    }
+
+    /// Whether an unfinished private game has `invite_code`.
+    #[tracing::instrument(skip(self))]
+    pub async fn invite_exists(&self, invite_code: &str) -> Result<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM game WHERE invite_code = $1 AND status != 'ended') as "exists!""#,
+            invite_code
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(exists)
+    }
+
+    /// Stores a finished game and its moves at once, for imported records.
+    #[tracing::instrument(skip(self))]
+    pub async fn import_game(&self, game: &Game, imported_by: &Uuid) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
         sqlx::query!(
-            r#"update game set winner = $2, x = $3, o = $4, status = $5, x_status = $6, o_status = $7 where id = $1"#,
+            "INSERT INTO game (id, room_id, x, o, init_player, game_type, status, board_size, rule_set, opening, opening_step, seed, winner, result, imported_by) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)",
             game.id,
-            serde_json::json!(game.winner),
+            game.room_id,
             game.x,
             game.o,
+            game.init_player as _,
+            game.game_type as _,
             game.status as _,
-            game.x_status as _,
-            game.o_status as _,
+            game.board_size as i16,
+            game.rule_set as _,
+            game.opening as _,
+            game.opening_step as _,
+            game.seed,
+            serde_json::json!(game.winner),
+            game.result as _,
+            imported_by
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        for (index, mv) in game.moves.iter().enumerate() {
+            sqlx::query!(
+                r#"insert into game_move(game_id, row, col, player, turn) values ($1, $2, $3, $4, $5)"#,
+                game.id,
+                mv.position.row as i32,
+                mv.position.col as i32,
+                mv.player as _,
+                (index + 1) as i32
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
         Ok(())
     }
 
     #[tracing::instrument(skip(self))]
-    pub async fn get_available_quick_games(&self, room_ids: &[Uuid]) -> Result<Game> {
-        let game = sqlx::query_as!(
+    pub async fn update_game(&self, game: &Game) -> Result<()> {
+        write_game(&self.pool, game).await
+    }
+
+    /// Writes a finished game and, if it is rated, its players' new ratings and a history row
+    /// for each, all at once. Saving the same result again leaves ratings alone.
+    #[tracing::instrument(skip(self))]
+    pub async fn save_result(&self, game: &Game) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        write_game(&mut *tx, game).await?;
+        let (Some(result), Some(x), Some(o)) = (game.result, game.x, game.o) else {
+            tx.commit().await?;
+            return Ok(());
+        };
+        let rated_already = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM rating_history WHERE game_id = $1) as "exists!""#,
+            game.id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if !rating::is_rated(game) || rated_already {
+            tx.commit().await?;
+            return Ok(());
+        }
+
+        sqlx::query!(
+            "INSERT INTO rating (user_id) VALUES ($1), ($2) ON CONFLICT DO NOTHING",
+            x,
+            o
+        )
+        .execute(&mut *tx)
+        .await?;
+        // Rows are locked in id order so two games finishing at once cannot deadlock.
+        let rows = sqlx::query!(
+            r#"
+            SELECT user_id, rating, deviation, volatility FROM rating
+            WHERE user_id = $1 OR user_id = $2
+            ORDER BY user_id
+            FOR UPDATE
+            "#,
+            x,
+            o
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let current = |user_id: Uuid| {
+            rows.iter()
+                .find(|row| row.user_id == user_id)
+                .map(|row| Rating {
+                    rating: row.rating,
+                    deviation: row.deviation,
+                    volatility: row.volatility,
+                })
+                .ok_or_else(|| anyhow!("no rating for {user_id}"))
+        };
+        let (x_rating, o_rating) = (current(x)?, current(o)?);
+        let updates = [
+            (
+                x,
+                x_rating,
+                x_rating.update(&o_rating, rating::score(result, Player::X)),
+            ),
+            (
+                o,
+                o_rating,
+                o_rating.update(&x_rating, rating::score(result, Player::O)),
+            ),
+        ];
+        for (user_id, before, after) in updates {
+            sqlx::query!(
+                r#"
+                UPDATE rating SET rating = $2, deviation = $3, volatility = $4,
+                    games = games + 1, updated_at = now()
+                WHERE user_id = $1
+                "#,
+                user_id,
+                after.rating,
+                after.deviation,
+                after.volatility
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query!(
+                r#"
+                INSERT INTO rating_history (game_id, user_id, rating, deviation, volatility, change)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+                game.id,
+                user_id,
+                after.rating,
+                after.deviation,
+                after.volatility,
+                after.rating - before.rating
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// `user_id`'s rating, or `None` before their first rated game.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_rating(&self, user_id: &Uuid) -> Result<Option<PlayerRating>> {
+        let rating = sqlx::query!(
+            "SELECT user_id, rating, deviation, volatility, games FROM rating WHERE user_id = $1",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|row| {
+            let rating = Rating {
+                rating: row.rating,
+                deviation: row.deviation,
+                volatility: row.volatility,
+            };
+            PlayerRating {
+                user_id: row.user_id,
+                rating,
+                games: row.games,
+                provisional: rating.is_provisional(),
+            }
+        });
+        Ok(rating)
+    }
+
+    /// `user_id`'s latest rating changes, newest first.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_rating_history(
+        &self,
+        user_id: &Uuid,
+        limit: i64,
+    ) -> Result<Vec<RatingChange>> {
+        let history = sqlx::query!(
+            r#"
+            SELECT game_id, rating, deviation, volatility, change, created_at as "created_at: DateTime<Utc>"
+            FROM rating_history
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+            user_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| RatingChange {
+            game_id: row.game_id,
+            rating: Rating {
+                rating: row.rating,
+                deviation: row.deviation,
+                volatility: row.volatility,
+            },
+            change: row.change,
+            created_at: row.created_at,
+        })
+        .collect();
+        Ok(history)
+    }
+
+    /// A page of rated players, highest rating first, and how many there are in all.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_leaderboard(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<PlayerRating>, i64)> {
+        let players = sqlx::query!(
+            r#"
+            SELECT user_id, rating, deviation, volatility, games FROM rating
+            ORDER BY rating DESC, user_id
+            LIMIT $1 OFFSET $2
+            "#,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let rating = Rating {
+                rating: row.rating,
+                deviation: row.deviation,
+                volatility: row.volatility,
+            };
+            PlayerRating {
+                user_id: row.user_id,
+                rating,
+                games: row.games,
+                provisional: rating.is_provisional(),
+            }
+        })
+        .collect();
+        let total = sqlx::query_scalar!(r#"SELECT count(*) as "count!" FROM rating"#)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok((players, total))
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn get_active_game_for_rooms(
+        &self,
+        room_ids: &[Uuid],
+        game_types: &[GameType],
+    ) -> Result<Vec<Game>> {
+        let games = sqlx::query_as!(
             GameDb,
             r#"
             SELECT
                 g.room_id,
                 g.id,
-                g.x_status as "x_status: PlayerStatus",
-                g.o_status as "o_status: PlayerStatus",
-                g.status as "status: GameStatus",
                 g.game_type as "game_type: GameType",
                 g.x,
+                g.x_status as "x_status: PlayerStatus",
+                g.status as "status: GameStatus",
+                g.o_status as "o_status: PlayerStatus",
                 g.o,
                 g.winner,
+                g.result as "result?: GameResult",
+                g.bot_level as "bot_level?: BotLevel",
+                g.seed,
                 g.init_player as "init_player: Player",
+                g.board_size,
+                g.rule_set as "rule_set: RuleSet",
+                g.opening as "opening: Opening",
+                g.opening_step as "opening_step: OpeningStep",
                 jsonb_agg(
                     jsonb_build_object(
                         'row', gm.row,
@@ -76,37 +376,92 @@ impl Db {
                 game_move gm
                 ON g.id = gm.game_id
             where g.room_id IN (SELECT unnest($1::uuid[])) and g.status != 'ended'
-            and ((g.x is null and g.o is not null) or (g.x is not null and g.o is null))
-            GROUP BY g.id
+            and g.game_type IN (select unnest($2::game_type[]))
+            GROUP BY
+                g.id;
+        "#,
+            room_ids,
+            game_types as _
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .filter_map(|game| Game::try_from(game).ok())
+        .collect();
+        Ok(games)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn get_active_game_for_room(&self, room_id: &Uuid) -> Result<Game> {
+        let game = sqlx::query_as!(
+            GameDb,
+            r#"
+            SELECT
+                g.room_id,
+                g.id,
+                g.x,
+                g.o,
+                g.status as "status: GameStatus",
+                g.x_status as "x_status: PlayerStatus",
+                g.o_status as "o_status: PlayerStatus",
+                g.winner,
+                g.result as "result?: GameResult",
+                g.bot_level as "bot_level?: BotLevel",
+                g.seed,
+                g.game_type as "game_type: GameType",
+                g.init_player as "init_player: Player",
+                g.board_size,
+                g.rule_set as "rule_set: RuleSet",
+                g.opening as "opening: Opening",
+                g.opening_step as "opening_step: OpeningStep",
+                jsonb_agg(
+                    jsonb_build_object(
+                        'row', gm.row,
+                        'col', gm.col,
+                        'player', gm.player
+                    ) ORDER BY gm.turn
+                ) AS moves
+            FROM
+                game g
+            LEFT JOIN
+                game_move gm
+                ON g.id = gm.game_id
+            where g.room_id = $1 and g.status != 'ended'
+            GROUP BY
+                g.id;
         "#,
-            room_ids
+            room_id,
         )
         .fetch_one(&self.pool)
         .await?;
+
         let game = Game::try_from(game)?;
         Ok(game)
     }
 
     #[tracing::instrument(skip(self))]
-    pub async fn get_active_game_for_rooms(
-        &self,
-        room_ids: &[Uuid],
-        game_types: &[GameType],
-    ) -> Result<Vec<Game>> {
-        let games = sqlx::query_as!(
+    pub async fn get_game(&self, game_id: &Uuid) -> Result<Game> {
+        let game = sqlx::query_as!(
             GameDb,
             r#"
             SELECT
                 g.room_id,
                 g.id,
-                g.game_type as "game_type: GameType",
                 g.x,
-                g.x_status as "x_status: PlayerStatus",
+                g.o,
                 g.status as "status: GameStatus",
+                g.x_status as "x_status: PlayerStatus",
                 g.o_status as "o_status: PlayerStatus",
-                g.o,
                 g.winner,
+                g.result as "result?: GameResult",
+                g.bot_level as "bot_level?: BotLevel",
+                g.seed,
+                g.game_type as "game_type: GameType",
                 g.init_player as "init_player: Player",
+                g.board_size,
+                g.rule_set as "rule_set: RuleSet",
+                g.opening as "opening: Opening",
+                g.opening_step as "opening_step: OpeningStep",
                 jsonb_agg(
                     jsonb_build_object(
                         'row', gm.row,
@@ -119,33 +474,103 @@ impl Db {
             LEFT JOIN
                 game_move gm
                 ON g.id = gm.game_id
-            where g.room_id IN (SELECT unnest($1::uuid[])) and g.status != 'ended'
-            and g.game_type IN (select unnest($2::game_type[]))
+            where g.id = $1
             GROUP BY
                 g.id;
         "#,
-            room_ids,
-            game_types as _
+            game_id,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let game = Game::try_from(game)?;
+        Ok(game)
+    }
+
+    /// A page of `user_id`'s finished or abandoned games, newest first, and how many there
+    /// are in all.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_game_history(
+        &self,
+        user_id: &Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<GameSummary>, i64)> {
+        let games = sqlx::query!(
+            r#"
+            SELECT
+                g.id,
+                g.game_type as "game_type: GameType",
+                g.x,
+                g.o,
+                g.bot_level as "bot_level?: BotLevel",
+                g.result as "result?: GameResult",
+                g.board_size,
+                g.rule_set as "rule_set: RuleSet",
+                g.created_at as "created_at: DateTime<Utc>",
+                g.imported_by,
+                (SELECT count(*) FROM game_move gm WHERE gm.game_id = g.id) as "moves!"
+            FROM
+                game g
+            WHERE (g.x = $1 OR g.o = $1 OR g.imported_by = $1)
+                AND (g.status = 'ended' OR g.result IS NOT NULL)
+            ORDER BY g.created_at DESC, g.id
+            LIMIT $2 OFFSET $3
+        "#,
+            user_id,
+            limit,
+            offset
         )
         .fetch_all(&self.pool)
         .await?
         .into_iter()
-        .filter_map(|game| {
-            let game = Game::try_from(game);
-            match game {
-                Ok(game) => Some(game),
-                _ => None,
+        .map(|game| {
+            let (colour, opponent) = if game.imported_by.is_some() {
+                (None, None)
+            } else if game.x == Some(*user_id) {
+                (Some(Player::X), game.o)
+            } else {
+                (Some(Player::O), game.x)
+            };
+            GameSummary {
+                id: game.id,
+                game_type: game.game_type,
+                colour,
+                opponent,
+                bot_level: game.bot_level,
+                result: game.result,
+                board_size: game.board_size as usize,
+                rule_set: game.rule_set,
+                moves: game.moves as usize,
+                created_at: game.created_at,
             }
         })
         .collect();
-        Ok(games)
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT count(*) as "count!" FROM game
+            WHERE (x = $1 OR o = $1 OR imported_by = $1)
+                AND (status = 'ended' OR result IS NOT NULL)
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok((games, total))
     }
 
+    /// Games won outright, by winners with at least `min_wins` such wins. Feeds the opening
+    /// book.
     #[tracing::instrument(skip(self))]
-    pub async fn get_active_game_for_room(&self, room_id: &Uuid) -> Result<Game> {
-        let game = sqlx::query_as!(
+    pub async fn get_won_games(&self, min_wins: i64) -> Result<Vec<Game>> {
+        let games = sqlx::query_as!(
             GameDb,
             r#"
+            WITH winners AS (
+                SELECT id, CASE WHEN result = 'x_wins' THEN x ELSE o END AS user_id
+                FROM game
+                WHERE result IN ('x_wins', 'o_wins') AND imported_by IS NULL
+            )
             SELECT
                 g.room_id,
                 g.id,
@@ -155,8 +580,15 @@ impl Db {
                 g.x_status as "x_status: PlayerStatus",
                 g.o_status as "o_status: PlayerStatus",
                 g.winner,
+                g.result as "result?: GameResult",
+                g.bot_level as "bot_level?: BotLevel",
+                g.seed,
                 g.game_type as "game_type: GameType",
                 g.init_player as "init_player: Player",
+                g.board_size,
+                g.rule_set as "rule_set: RuleSet",
+                g.opening as "opening: Opening",
+                g.opening_step as "opening_step: OpeningStep",
                 jsonb_agg(
                     jsonb_build_object(
                         'row', gm.row,
@@ -166,20 +598,26 @@ impl Db {
                 ) AS moves
             FROM
                 game g
-            LEFT JOIN
+            JOIN
+                winners w
+                ON g.id = w.id
+            JOIN
                 game_move gm
                 ON g.id = gm.game_id
-            where g.room_id = $1 and g.status != 'ended'
+            where w.user_id IN (
+                SELECT user_id FROM winners GROUP BY user_id HAVING count(*) >= $1
+            )
             GROUP BY
                 g.id;
         "#,
-            room_id,
+            min_wins,
         )
-        .fetch_one(&self.pool)
-        .await?;
-
-        let game = Game::try_from(game)?;
-        Ok(game)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .filter_map(|game| Game::try_from(game).ok())
+        .collect();
+        Ok(games)
     }
 
     // pub async fn end_game(&self, id: Uuid) -> Result<()> {
@@ -203,4 +641,130 @@ impl Db {
         .await?;
         Ok(())
     }
+
+    /// Records a hint for `user_id` unless they already used `limit` in this game. Returns
+    /// whether it was recorded.
+    #[tracing::instrument(skip(self))]
+    pub async fn insert_hint(
+        &self,
+        game_id: &Uuid,
+        user_id: &Uuid,
+        turn: usize,
+        limit: usize,
+    ) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            insert into hint(game_id, user_id, turn)
+            select $1, $2, $3
+            where (select count(*) from hint where game_id = $1 and user_id = $2) < $4
+            "#,
+            game_id,
+            user_id,
+            turn as i32,
+            i64::try_from(limit).unwrap_or(i64::MAX)
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Hints `user_id` used in the game.
+    #[tracing::instrument(skip(self))]
+    pub async fn count_hints(&self, game_id: &Uuid, user_id: &Uuid) -> Result<usize> {
+        let count = sqlx::query_scalar!(
+            r#"select count(*) as "count!" from hint where game_id = $1 and user_id = $2"#,
+            game_id,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count as usize)
+    }
+
+    /// Stores a game's analysis. Turns already stored are kept.
+    #[tracing::instrument(skip(self, analysis))]
+    pub async fn insert_analysis(&self, game_id: &Uuid, analysis: &[MoveAnalysis]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for entry in analysis {
+            sqlx::query!(
+                r#"
+                insert into move_analysis(game_id, turn, player, row, col, best_row, best_col, best_score, played_score, class)
+                values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                on conflict (game_id, turn) do nothing
+                "#,
+                game_id,
+                entry.turn as i32,
+                entry.mv.player as _,
+                entry.mv.position.row as i16,
+                entry.mv.position.col as i16,
+                entry.best.row as i16,
+                entry.best.col as i16,
+                entry.best_score,
+                entry.played_score,
+                entry.class as _
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// A game's analysis in move order; empty if it has not been analysed.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_analysis(&self, game_id: &Uuid) -> Result<Vec<MoveAnalysis>> {
+        let analysis = sqlx::query!(
+            r#"
+            select
+                turn,
+                player as "player: Player",
+                row,
+                col,
+                best_row,
+                best_col,
+                best_score,
+                played_score,
+                class as "class: MoveClass"
+            from move_analysis
+            where game_id = $1
+            order by turn
+            "#,
+            game_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| MoveAnalysis {
+            turn: row.turn as usize,
+            mv: Move::new(
+                row.player,
+                Position::new(row.col as usize, row.row as usize),
+            ),
+            best: Position::new(row.best_col as usize, row.best_row as usize),
+            best_score: row.best_score,
+            played_score: row.played_score,
+            swing: (row.best_score - row.played_score).max(0),
+            class: row.class,
+        })
+        .collect();
+        Ok(analysis)
+    }
+}
+
+async fn write_game(executor: impl PgExecutor<'_>, game: &Game) -> Result<()> {
+    sqlx::query!(
+        r#"update game set winner = $2, x = $3, o = $4, status = $5, x_status = $6, o_status = $7, opening_step = $8, result = $9 where id = $1"#,
+        game.id,
+        serde_json::json!(game.winner),
+        game.x,
+        game.o,
+        game.status as _,
+        game.x_status as _,
+        game.o_status as _,
+        game.opening_step as _,
+        game.result as _,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
 }
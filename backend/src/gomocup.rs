@@ -0,0 +1,282 @@
+//! The Gomocup (Piskvork) text protocol, so the bot can play in standard tournament managers.
+//!
+//! The manager sends one command per line and the engine answers with at most one line.
+//! Coordinates are `x,y` with `x` the column and `y` the row, both from zero. The engine
+//! always plays the side to move, so it never has to be told its colour.
+
+use crate::models::{
+    BotLevel, BotSettings, Game, GameType, Move, Player, Position, RuleSet, MAX_BOARD_SIZE,
+    MIN_BOARD_SIZE,
+};
+use crate::profile::EvalProfile;
+use crate::transposition::TranspositionTable;
+use std::fmt::Display;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Time kept back from every turn for parsing, output and the manager's own overhead.
+const TURN_MARGIN: Duration = Duration::from_millis(100);
+/// Share of the remaining match time one move may use.
+const MATCH_TIME_SHARE: u32 = 20;
+
+/// What the engine answers to a command.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reply {
+    Line(String),
+    /// Nothing to print, e.g. for `INFO` or while a `BOARD` block is being read.
+    Silent,
+    /// `END`: the engine should exit.
+    End,
+}
+
+pub struct Engine {
+    level: BotLevel,
+    profile: Arc<EvalProfile>,
+    game: Option<Game>,
+    rule_set: RuleSet,
+    timeout_turn: Option<Duration>,
+    time_left: Option<Duration>,
+    /// Stones listed so far in a `BOARD` block, as `(position, field)`.
+    board: Option<Vec<(Position, u8)>>,
+    transposition_table: TranspositionTable,
+}
+
+impl Engine {
+    pub fn new(level: BotLevel) -> Self {
+        Self {
+            level,
+            profile: EvalProfile::shared_default(),
+            game: None,
+            rule_set: RuleSet::Freestyle,
+            timeout_turn: None,
+            time_left: None,
+            board: None,
+            transposition_table: TranspositionTable::default(),
+        }
+    }
+
+    /// Scores positions with `profile` instead of the built-in one.
+    pub fn set_profile(&mut self, profile: Arc<EvalProfile>) {
+        self.profile = profile;
+    }
+
+    pub fn handle(&mut self, line: &str) -> Reply {
+        let line = line.trim();
+        if self.board.is_some() {
+            return self.board_line(line);
+        }
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        match command.to_ascii_uppercase().as_str() {
+            "START" => match args.parse() {
+                Ok(size) if (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&size) => {
+                    self.start(size);
+                    Reply::Line("OK".to_string())
+                }
+                _ => error(format!("unsupported size {args}")),
+            },
+            "RESTART" => match self.game.as_ref().map(|game| game.board_size) {
+                Some(size) => {
+                    self.start(size);
+                    Reply::Line("OK".to_string())
+                }
+                None => error("no game started"),
+            },
+            "BEGIN" => match &self.game {
+                Some(game) if game.moves.is_empty() => self.reply(),
+                Some(_) => error("board is not empty"),
+                None => error("no game started"),
+            },
+            "TURN" => {
+                let Some(pos) = parse_position(args) else {
+                    return error(format!("invalid move {args}"));
+                };
+                let Some(game) = &mut self.game else {
+                    return error("no game started");
+                };
+                if let Err(err) = game.play(&Move::new(game.next_player, pos)) {
+                    return error(err.to_string());
+                }
+                self.reply()
+            }
+            "BOARD" => {
+                if self.game.is_none() {
+                    return error("no game started");
+                }
+                self.board = Some(vec![]);
+                Reply::Silent
+            }
+            "INFO" => {
+                self.info(args);
+                Reply::Silent
+            }
+            "ABOUT" => Reply::Line(format!(
+                "name=\"gomoku\", version=\"{}\"",
+                env!("CARGO_PKG_VERSION")
+            )),
+            "END" => Reply::End,
+            "" => Reply::Silent,
+            _ => Reply::Line(format!("UNKNOWN {command}")),
+        }
+    }
+
+    fn start(&mut self, size: usize) {
+        let mut game = Game::new(Uuid::nil(), Player::X, GameType::Bot, size);
+        game.rule_set = self.rule_set;
+        game.x = Some(Uuid::nil());
+        game.o = Some(Uuid::nil());
+        self.game = Some(game);
+        self.transposition_table.clear();
+    }
+
+    /// `INFO key value`. Unknown keys are ignored, as the protocol asks.
+    fn info(&mut self, args: &str) {
+        let (key, value) = args.split_once(' ').unwrap_or((args, ""));
+        let millis = value.trim().parse::<u64>().ok().map(Duration::from_millis);
+        match key.to_ascii_lowercase().as_str() {
+            // 0 means "as fast as possible", which the level's own budget already is.
+            "timeout_turn" => self.timeout_turn = millis.filter(|time| !time.is_zero()),
+            "time_left" => self.time_left = millis,
+            "rule" => {
+                if let Ok(rule) = value.trim().parse::<u32>() {
+                    self.rule_set = rule_set(rule);
+                    if let Some(game) = &mut self.game {
+                        game.rule_set = self.rule_set;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// A line of a `BOARD` block: `x,y,field` with field 1 for own stones and 2 for the
+    /// opponent's, or `DONE`. Other fields only matter in continuous games and are skipped.
+    fn board_line(&mut self, line: &str) -> Reply {
+        if !line.eq_ignore_ascii_case("DONE") {
+            let stone = line.rsplit_once(',').and_then(|(pos, field)| {
+                Some((parse_position(pos)?, field.trim().parse::<u8>().ok()?))
+            });
+            return match (stone, &mut self.board) {
+                (Some(stone), Some(board)) => {
+                    board.push(stone);
+                    Reply::Silent
+                }
+                _ => error(format!("invalid board line {line}")),
+            };
+        }
+        let stones = self.board.take().unwrap_or_default();
+        let Some(size) = self.game.as_ref().map(|game| game.board_size) else {
+            return error("no game started");
+        };
+        self.start(size);
+        match self.set_up(&stones) {
+            Ok(()) => self.reply(),
+            Err(message) => error(message),
+        }
+    }
+
+    /// Replays a `BOARD` block. The engine is to move, so it played first when both sides
+    /// have as many stones, and second when the opponent has one more.
+    fn set_up(&mut self, stones: &[(Position, u8)]) -> Result<(), String> {
+        let own: Vec<Position> = stones
+            .iter()
+            .filter(|(_, field)| *field == 1)
+            .map(|(pos, _)| *pos)
+            .collect();
+        let theirs: Vec<Position> = stones
+            .iter()
+            .filter(|(_, field)| *field == 2)
+            .map(|(pos, _)| *pos)
+            .collect();
+        let (first, second) = match theirs.len().checked_sub(own.len()) {
+            Some(0) => (own, theirs),
+            Some(1) => (theirs, own),
+            _ => return Err("stone counts do not add up".to_string()),
+        };
+        let game = self.game.as_mut().ok_or("no game started")?;
+        let mut second = second.into_iter();
+        for pos in first {
+            place(game, pos)?;
+            if let Some(pos) = second.next() {
+                place(game, pos)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Searches for the side to move, plays the move and prints it.
+    fn reply(&mut self) -> Reply {
+        let settings = self.settings();
+        let Some(game) = &mut self.game else {
+            return error("no game started");
+        };
+        let cancelled = AtomicBool::new(false);
+        let Some(pos) = game.find_bot_move(&settings, &cancelled, &self.transposition_table) else {
+            return error("no move found");
+        };
+        if let Err(err) = game.play(&Move::new(game.next_player, pos)) {
+            return error(err.to_string());
+        }
+        Reply::Line(format!("{},{}", pos.col, pos.row))
+    }
+
+    /// The level's settings with the time budget cut to what the manager allows.
+    pub fn settings(&self) -> BotSettings {
+        let mut settings = self.level.settings();
+        settings.profile = self.profile.clone();
+        let mut budget = self.timeout_turn.unwrap_or(settings.time_budget);
+        if let Some(time_left) = self.time_left {
+            budget = budget.min(time_left / MATCH_TIME_SHARE);
+        }
+        settings.time_budget = budget.saturating_sub(TURN_MARGIN);
+        settings
+    }
+
+    pub fn game(&self) -> Option<&Game> {
+        self.game.as_ref()
+    }
+}
+
+/// Places a stone from a `BOARD` block. Stones are taken as given, so forbidden-move rules are
+/// not checked against them.
+fn place(game: &mut Game, pos: Position) -> Result<(), String> {
+    if pos.col >= game.board_size || pos.row >= game.board_size {
+        return Err(format!("{},{} is off the board", pos.col, pos.row));
+    }
+    if game.board[pos.row][pos.col].is_some() {
+        return Err(format!("{},{} is taken twice", pos.col, pos.row));
+    }
+    let player = game.next_player;
+    game.place(&pos, player);
+    game.moves.push(Move::new(player, pos));
+    game.next_player = player.opponent();
+    Ok(())
+}
+
+/// The `INFO rule` bit mask: 1 exactly five, 4 renju, 8 caro. Bit 2 (continuous game) only
+/// matters to the manager.
+fn rule_set(rule: u32) -> RuleSet {
+    if rule & 4 != 0 {
+        RuleSet::Renju
+    } else if rule & 8 != 0 {
+        RuleSet::Caro
+    } else if rule & 1 != 0 {
+        RuleSet::Standard
+    } else {
+        RuleSet::Freestyle
+    }
+}
+
+fn parse_position(text: &str) -> Option<Position> {
+    let (col, row) = text.split_once(',')?;
+    Some(Position::new(
+        col.trim().parse().ok()?,
+        row.trim().parse().ok()?,
+    ))
+}
+
+fn error(message: impl Display) -> Reply {
+    Reply::Line(format!("ERROR {message}"))
+}
@@ -1,4 +1,15 @@
+pub mod analysis;
 pub mod api;
 pub mod auth;
+pub mod bitboard;
+pub mod book;
 pub mod db;
+pub mod gomocup;
+pub mod matchmaking;
 pub mod models;
+pub mod profile;
+pub mod rating;
+pub mod record;
+pub mod solver;
+pub mod tournament;
+pub mod transposition;
@@ -1,8 +1,11 @@
 use {
-    backend::api,
+    backend::api::{self, AppOptions},
+    backend::book::OpeningBook,
+    backend::profile::EvalProfiles,
     opentelemetry::global,
     serde::Deserialize,
     sqlx::PgPool,
+    std::time::Duration,
     tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt},
 };
 
@@ -14,6 +17,23 @@ lazy_static::lazy_static! {
 pub struct Config {
     pub database_url: String,
     pub jwt_secret: String,
+    /// Path of an opening book file for the bot.
+    pub opening_book: Option<String>,
+    /// Path of a TOML or JSON file with evaluation profiles per bot level.
+    pub eval_profiles: Option<String>,
+    /// Threads per bot search. Defaults to 1.
+    pub search_threads: Option<usize>,
+    /// Bot searches allowed at once across all rooms. Defaults to the number of cores.
+    pub max_concurrent_searches: Option<usize>,
+    /// Accept client-chosen RNG seeds for games and search to a fixed depth, so bot games
+    /// replay exactly. Off by default.
+    pub debug: Option<bool>,
+    /// Seconds a player may be away from a game under way before losing on time. Defaults
+    /// to 60.
+    pub abandon_grace_secs: Option<u64>,
+    /// Hint policies per game type as JSON, e.g.
+    /// `{"bot": {"per_game": 10, "max_candidates": 5}}`. Replaces the defaults.
+    pub hints: Option<String>,
 }
 
 #[tokio::main]
@@ -51,7 +71,45 @@ async fn main() {
     if let Err(error) = sqlx::migrate!("./migrations").run(&pool).await {
         tracing::error!(?error);
     }
-    axum::serve(listener, api::app(pool, &CONFIG.jwt_secret))
-        .await
-        .expect("Failed to run server");
+    let opening_book = match &CONFIG.opening_book {
+        Some(path) => OpeningBook::load(path).unwrap_or_else(|error| {
+            tracing::error!(?error, "Can't load opening book");
+            OpeningBook::default()
+        }),
+        None => OpeningBook::default(),
+    };
+    let eval_profiles = match &CONFIG.eval_profiles {
+        Some(path) => EvalProfiles::load(path).unwrap_or_else(|error| {
+            tracing::error!(?error, "Can't load evaluation profiles");
+            EvalProfiles::default()
+        }),
+        None => EvalProfiles::default(),
+    };
+    let defaults = AppOptions::default();
+    let hints = match &CONFIG.hints {
+        Some(hints) => serde_json::from_str(hints).unwrap_or_else(|error| {
+            tracing::error!(?error, "Can't parse hint policies");
+            defaults.hints.clone()
+        }),
+        None => defaults.hints.clone(),
+    };
+    let options = AppOptions {
+        opening_book,
+        eval_profiles,
+        search_threads: CONFIG.search_threads.unwrap_or(defaults.search_threads),
+        max_concurrent_searches: CONFIG
+            .max_concurrent_searches
+            .unwrap_or(defaults.max_concurrent_searches),
+        debug: CONFIG.debug.unwrap_or(defaults.debug),
+        abandon_grace: CONFIG
+            .abandon_grace_secs
+            .map_or(defaults.abandon_grace, Duration::from_secs),
+        hints,
+    };
+    axum::serve(
+        listener,
+        api::app_with_options(pool, &CONFIG.jwt_secret, options),
+    )
+    .await
+    .expect("Failed to run server");
 }
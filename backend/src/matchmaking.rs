@@ -0,0 +1,156 @@
+//! The quick-match queue: players wait with the settings they want and are paired with the
+//! closest rating in reach.
+//!
+//! Each player's reach, the band, starts narrow and widens the longer they wait. Two players
+//! are paired when each is within the other's band. Pairing takes both tickets out of the
+//! queue at once, so a player can never be matched twice.
+
+use crate::models::{Opening, Player, RuleSet};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// Rating points either side a player reaches as soon as they join.
+const BASE_BAND: f64 = 100.0;
+/// Rating points the band widens by per second of waiting.
+const BAND_GROWTH: f64 = 25.0;
+/// The widest band, reached after about half a minute.
+const MAX_BAND: f64 = 1000.0;
+
+/// What a player asks to be matched for. Only players asking for the same are paired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct MatchSettings {
+    pub board_size: usize,
+    #[serde(default)]
+    pub rule_set: RuleSet,
+    #[serde(default)]
+    pub opening: Opening,
+}
+
+/// Sent to a queued player over the matchmaking websocket.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum MatchEvent {
+    Queued { rating: f64 },
+    Matched { room: Uuid, colour: Player },
+}
+
+#[derive(Debug)]
+pub struct Ticket {
+    pub user_id: Uuid,
+    pub rating: f64,
+    pub settings: MatchSettings,
+    pub joined: Instant,
+    /// Receives the room once the player is matched. Closed when the player has left.
+    pub notify: oneshot::Sender<MatchEvent>,
+}
+
+impl Ticket {
+    pub fn new(
+        user_id: Uuid,
+        rating: f64,
+        settings: MatchSettings,
+    ) -> (Self, oneshot::Receiver<MatchEvent>) {
+        let (notify, matched) = oneshot::channel();
+        let ticket = Self {
+            user_id,
+            rating,
+            settings,
+            joined: Instant::now(),
+            notify,
+        };
+        (ticket, matched)
+    }
+
+    /// How far from its rating the ticket reaches at `now`.
+    pub fn band(&self, now: Instant) -> f64 {
+        let waited = now.saturating_duration_since(self.joined);
+        band(waited)
+    }
+
+    fn reaches(&self, other: &Ticket, now: Instant) -> bool {
+        let gap = (self.rating - other.rating).abs();
+        self.settings == other.settings
+            && self.user_id != other.user_id
+            && gap <= self.band(now)
+            && gap <= other.band(now)
+    }
+}
+
+pub fn band(waited: Duration) -> f64 {
+    (BASE_BAND + BAND_GROWTH * waited.as_secs_f64()).min(MAX_BAND)
+}
+
+#[derive(Debug, Default)]
+pub struct Queue {
+    tickets: Vec<Ticket>,
+}
+
+impl Queue {
+    /// Queues `ticket`, replacing any ticket the player already had.
+    pub fn push(&mut self, ticket: Ticket) {
+        self.remove(&ticket.user_id);
+        self.tickets.push(ticket);
+    }
+
+    pub fn remove(&mut self, user_id: &Uuid) {
+        self.tickets.retain(|ticket| ticket.user_id != *user_id);
+    }
+
+    /// Drops the tickets of players who have left.
+    pub fn remove_closed(&mut self) {
+        self.tickets.retain(|ticket| !ticket.notify.is_closed());
+    }
+
+    pub fn len(&self) -> usize {
+        self.tickets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tickets.is_empty()
+    }
+
+    /// Takes out every pair that can be made at `now`, and drops tickets whose player has
+    /// left. The longest waiting players choose first, each taking the closest rating in
+    /// reach.
+    pub fn pair(&mut self, now: Instant) -> Vec<(Ticket, Ticket)> {
+        self.remove_closed();
+        self.tickets.sort_by_key(|ticket| ticket.joined);
+        let mut partners: Vec<Option<usize>> = vec![None; self.tickets.len()];
+        for first in 0..self.tickets.len() {
+            if partners[first].is_some() {
+                continue;
+            }
+            let ticket = &self.tickets[first];
+            let second = (first + 1..self.tickets.len())
+                .filter(|&other| partners[other].is_none())
+                .filter(|&other| ticket.reaches(&self.tickets[other], now))
+                .min_by(|&a, &b| {
+                    let gap = |other: usize| (ticket.rating - self.tickets[other].rating).abs();
+                    gap(a).total_cmp(&gap(b))
+                });
+            if let Some(second) = second {
+                partners[first] = Some(second);
+                partners[second] = Some(first);
+            }
+        }
+
+        let mut pairs = vec![];
+        let mut waiting = vec![];
+        let mut taken: Vec<Option<Ticket>> = self.tickets.drain(..).map(Some).collect();
+        for index in 0..taken.len() {
+            match partners[index] {
+                Some(partner) if partner > index => {
+                    let first = taken[index].take().unwrap();
+                    let second = taken[partner].take().unwrap();
+                    pairs.push((first, second));
+                }
+                Some(_) => {}
+                None => waiting.extend(taken[index].take()),
+            }
+        }
+        self.tickets = waiting;
+        pairs
+    }
+}
@@ -1,15 +1,35 @@
+use crate::bitboard::BitBoard;
+use crate::profile::EvalProfile;
+use crate::solver;
+use crate::transposition::{self, Bound, TranspositionTable, TtEntry};
 use anyhow::Result;
-use rand::Rng;
+use chrono::{DateTime, Utc};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 const WINNING_MOVE_COUNT: usize = 5;
 const MAX_SCORE: i32 = 500;
-const BOARD_SIZE: usize = 15;
-
-#[derive(Debug, sqlx::Type, Serialize, Deserialize, Clone)]
+/// Scores at least this far up are forced wins; a win found `n` plies deep scores
+/// `MAX_SCORE - n`.
+pub const WIN_SCORE: i32 = MAX_SCORE - 16;
+pub const DEFAULT_BOARD_SIZE: usize = 15;
+pub const MIN_BOARD_SIZE: usize = 9;
+pub const MAX_BOARD_SIZE: usize = 30;
+/// How many levels of "is the completing move itself forbidden" to follow when checking
+/// Renju threes.
+const RENJU_THREE_DEPTH: usize = 2;
+const DIRECTIONS: [(isize, isize); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+
+#[derive(Debug, sqlx::Type, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 #[sqlx(type_name = "game_type", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum GameType {
@@ -37,30 +57,286 @@ pub enum GameStatus {
     Ended,
 }
 
+#[derive(Debug, sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[sqlx(type_name = "bot_level", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum BotLevel {
+    Beginner,
+    Casual,
+    #[default]
+    Strong,
+    Expert,
+}
+
+/// How hard the bot tries.
+#[derive(Debug, Clone)]
+pub struct BotSettings {
+    /// Deepest iteration searched below each root candidate.
+    pub depth: i32,
+    /// Wall-clock time after which the search stops deepening.
+    pub time_budget: Duration,
+    /// Candidates considered at every node, most threatening first.
+    pub max_candidates: usize,
+    /// Chance of playing a random nearby move instead of searching.
+    pub blunder_rate: f64,
+    /// Longest forced win by fours the bot looks for, for itself and its opponent. 0 skips it.
+    pub vcf_depth: usize,
+    /// Longest forced win by fours and threes the bot looks for. 0 skips it.
+    pub vct_depth: usize,
+    /// The shapes positions are scored by.
+    pub profile: Arc<EvalProfile>,
+    /// Threads searching each move, sharing one transposition table.
+    pub threads: usize,
+    /// Searches every iteration up to `depth` whatever the clock says, on this thread alone,
+    /// so the reply depends only on the position and the seed. For replaying games exactly.
+    pub fixed_depth: bool,
+}
+
+impl BotLevel {
+    pub fn settings(&self) -> BotSettings {
+        match self {
+            BotLevel::Beginner => BotSettings {
+                time_budget: Duration::from_millis(100),
+                depth: 0,
+                max_candidates: 6,
+                vcf_depth: 0,
+                vct_depth: 0,
+                blunder_rate: 0.3,
+                profile: EvalProfile::shared_default(),
+                threads: 1,
+                fixed_depth: false,
+            },
+            BotLevel::Casual => BotSettings {
+                time_budget: Duration::from_millis(250),
+                depth: 2,
+                max_candidates: 10,
+                vcf_depth: 4,
+                vct_depth: 0,
+                blunder_rate: 0.1,
+                profile: EvalProfile::shared_default(),
+                threads: 1,
+                fixed_depth: false,
+            },
+            BotLevel::Strong => BotSettings {
+                time_budget: Duration::from_millis(500),
+                depth: 4,
+                max_candidates: 16,
+                vcf_depth: 8,
+                vct_depth: 2,
+                blunder_rate: 0.0,
+                profile: EvalProfile::shared_default(),
+                threads: 1,
+                fixed_depth: false,
+            },
+            BotLevel::Expert => BotSettings {
+                time_budget: Duration::from_millis(1500),
+                depth: 8,
+                max_candidates: 24,
+                vcf_depth: 12,
+                vct_depth: 3,
+                blunder_rate: 0.0,
+                profile: EvalProfile::shared_default(),
+                threads: 1,
+                fixed_depth: false,
+            },
+        }
+    }
+}
+
+/// How a bot search scores positions and when it has to stop: out of time, or cancelled
+/// because the room closed.
+pub struct SearchLimits<'a> {
+    max_candidates: usize,
+    /// `None` for a fixed-depth search.
+    deadline: Option<Instant>,
+    cancelled: &'a AtomicBool,
+    profile: &'a EvalProfile,
+    /// The side the search plays for, whose shapes the profile counts as its own.
+    bot: Player,
+}
+
+impl<'a> SearchLimits<'a> {
+    pub fn new(settings: &'a BotSettings, bot: Player, cancelled: &'a AtomicBool) -> Self {
+        Self {
+            max_candidates: settings.max_candidates,
+            deadline: (!settings.fixed_depth).then(|| Instant::now() + settings.time_budget),
+            cancelled,
+            profile: &settings.profile,
+            bot,
+        }
+    }
+
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.cancelled.load(AtomicOrdering::Relaxed)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+#[derive(Debug, sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "game_result", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum GameResult {
+    XWins,
+    OWins,
+    XResigned,
+    OResigned,
+    XTimedOut,
+    OTimedOut,
+    DrawFullBoard,
+    DrawAgreement,
+}
+
+impl GameResult {
+    pub fn win(player: Player) -> Self {
+        match player {
+            Player::X => GameResult::XWins,
+            Player::O => GameResult::OWins,
+        }
+    }
+
+    pub fn resign(player: Player) -> Self {
+        match player {
+            Player::X => GameResult::XResigned,
+            Player::O => GameResult::OResigned,
+        }
+    }
+
+    pub fn time_out(player: Player) -> Self {
+        match player {
+            Player::X => GameResult::XTimedOut,
+            Player::O => GameResult::OTimedOut,
+        }
+    }
+
+    pub fn winner(&self) -> Option<Player> {
+        match self {
+            GameResult::XWins | GameResult::OResigned | GameResult::OTimedOut => Some(Player::X),
+            GameResult::OWins | GameResult::XResigned | GameResult::XTimedOut => Some(Player::O),
+            GameResult::DrawFullBoard | GameResult::DrawAgreement => None,
+        }
+    }
+}
+
+#[derive(Debug, sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[sqlx(type_name = "rule_set", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum RuleSet {
+    /// Five or more in a row wins.
+    #[default]
+    Freestyle,
+    /// Exactly five wins; an overline is not a win for either side.
+    Standard,
+    /// Black wins with exactly five and may not play 3-3, 4-4 or an overline.
+    /// White wins with five or more.
+    Renju,
+    /// Five or more wins unless both ends are blocked by the opponent.
+    Caro,
+}
+
+#[derive(Debug, sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[sqlx(type_name = "opening", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Opening {
+    /// The first player simply moves first.
+    #[default]
+    Free,
+    /// Black opens in the centre and black's second stone must be at least three lines away
+    /// from it.
+    Pro,
+    /// The opener places three stones, then the opponent picks a colour.
+    Swap,
+    /// Like swap, but the opponent may instead place two more stones and let the opener pick.
+    Swap2,
+}
+
+#[derive(Debug, sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "opening_step", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum OpeningStep {
+    /// The opener places the first three stones, two black and one white.
+    Propose,
+    /// The opponent picks a colour, or under swap2 may place two more stones instead.
+    Choose,
+    /// Swap2: the opponent places the fourth and fifth stones.
+    Extend,
+    /// Swap2: the opener picks a colour after the extension.
+    ChooseAfterExtend,
+    Done,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ForbiddenMove {
+    DoubleThree,
+    DoubleFour,
+    Overline,
+}
+
+impl Display for ForbiddenMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ForbiddenMove::DoubleThree => write!(f, "Forbidden move: double three"),
+            ForbiddenMove::DoubleFour => write!(f, "Forbidden move: double four"),
+            ForbiddenMove::Overline => write!(f, "Forbidden move: overline"),
+        }
+    }
+}
+
+impl std::error::Error for ForbiddenMove {}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Game {
     pub id: Uuid,
-    pub board: [[Option<Player>; BOARD_SIZE]; BOARD_SIZE],
+    pub board: Vec<Vec<Option<Player>>>,
+    pub board_size: usize,
+    pub rule_set: RuleSet,
+    pub init_player: Player,
+    pub opening: Opening,
+    pub opening_step: OpeningStep,
     pub x: Option<Uuid>,
     pub o: Option<Uuid>,
     pub next_player: Player,
     pub moves: Vec<Move>,
     pub winner: Option<Vec<Move>>,
+    pub result: Option<GameResult>,
     pub x_status: PlayerStatus,
     pub o_status: PlayerStatus,
     pub game_type: GameType,
+    pub bot_level: Option<BotLevel>,
+    /// Seeds the bot's random choices, so the same moves always get the same replies. Not
+    /// sent to clients, who could otherwise predict the bot.
+    #[serde(skip)]
+    pub seed: i64,
+    /// Code that seats its holder in a private game. Only set on games being created; it is
+    /// not read back from the database.
+    #[serde(skip)]
+    pub invite_code: Option<String>,
     pub room_id: Uuid,
     pub status: GameStatus,
+    /// Zobrist hash of `board`, kept up to date by `place` and `remove`.
+    #[serde(skip)]
+    hash: u64,
+    /// Line bit masks mirroring `board`, kept up to date by `place` and `remove`.
+    #[serde(skip)]
+    pub(crate) bits: BitBoard,
 }
 
 impl Game {
-    pub fn new(room_id: Uuid, next_player: Player, game_type: GameType) -> Self {
+    pub fn new(room_id: Uuid, next_player: Player, game_type: GameType, board_size: usize) -> Self {
         Self {
             id: Uuid::new_v4(),
-            board: [[None; BOARD_SIZE]; BOARD_SIZE],
+            board: empty_board(board_size),
+            board_size,
+            rule_set: RuleSet::default(),
+            init_player: next_player,
+            opening: Opening::default(),
+            opening_step: OpeningStep::Done,
             x: None,
             o: None,
             winner: None,
+            result: None,
             next_player,
             moves: vec![],
             x_status: PlayerStatus::Ready,
@@ -71,12 +347,252 @@ impl Game {
                 GameType::Normal | GameType::Private => GameStatus::Ready,
             },
             game_type,
+            bot_level: None,
+            seed: rand::random(),
+            invite_code: None,
+            hash: 0,
+            bits: BitBoard::new(board_size),
+        }
+    }
+
+    /// A game set up from a board diagram: one row per line or `/`-separated, `x` and `o` for
+    /// stones and `.` for empty cells. X is taken to have moved first, so X is to move when
+    /// both have as many stones. Stones are placed as given, without checking forbidden moves.
+    pub fn from_board(diagram: &str, rule_set: RuleSet) -> Result<Self> {
+        let rows: Vec<&str> = diagram
+            .split(['/', '\n'])
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+            .collect();
+        let board_size = rows.len();
+        if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&board_size) {
+            return Err(anyhow::anyhow!("Unsupported board size {board_size}"));
+        }
+        let (mut xs, mut os) = (vec![], vec![]);
+        for (row, line) in rows.iter().enumerate() {
+            if line.chars().count() != board_size {
+                return Err(anyhow::anyhow!("Row {row} is not {board_size} cells long"));
+            }
+            for (col, cell) in line.chars().enumerate() {
+                match cell.to_ascii_lowercase() {
+                    'x' => xs.push(Position::new(col, row)),
+                    'o' => os.push(Position::new(col, row)),
+                    '.' => {}
+                    _ => return Err(anyhow::anyhow!("Invalid cell {cell:?}")),
+                }
+            }
+        }
+        let next_player = match xs.len().checked_sub(os.len()) {
+            Some(0) => Player::X,
+            Some(1) => Player::O,
+            _ => return Err(anyhow::anyhow!("Stone counts do not add up")),
+        };
+        let mut game = Game::new(Uuid::nil(), Player::X, GameType::Bot, board_size);
+        game.rule_set = rule_set;
+        game.x = Some(Uuid::nil());
+        game.o = Some(Uuid::nil());
+        let mut os = os.into_iter();
+        for pos in xs {
+            game.place(&pos, Player::X);
+            game.moves.push(Move::new(Player::X, pos));
+            if let Some(pos) = os.next() {
+                game.place(&pos, Player::O);
+                game.moves.push(Move::new(Player::O, pos));
+            }
+        }
+        game.next_player = next_player;
+        Ok(game)
+    }
+
+    /// The static evaluation from the side to move's point of view, and the threats on the
+    /// board, strongest first.
+    pub fn static_evaluation(&mut self, settings: &BotSettings) -> (i32, Vec<Threat>) {
+        let cancelled = AtomicBool::new(false);
+        let limits = SearchLimits::new(settings, self.next_player, &cancelled);
+        let (score, threats) = self.evaluate(&limits);
+        let score = if self.next_player == Player::O {
+            score
+        } else {
+            -score
+        };
+        let mut found: Vec<Threat> = vec![];
+        for (stones, position, player) in threats {
+            let threat = Threat {
+                stones,
+                position,
+                player,
+            };
+            if !found.contains(&threat) {
+                found.push(threat);
+            }
+        }
+        (score, found)
+    }
+
+    /// The moves in notation, the first with its player, e.g. `Xh8 i9 h9`.
+    pub fn notation(&self) -> String {
+        self.moves
+            .iter()
+            .enumerate()
+            .map(|(index, mv)| {
+                if index == 0 {
+                    mv.to_notation(self.board_size)
+                } else {
+                    mv.position.to_notation(self.board_size)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Whether a five is already on the board.
+    pub fn has_five(&self) -> bool {
+        self.moves
+            .iter()
+            .any(|mv| matches!(self.check_winning_move(&mv.position), Ok(Some(_))))
+    }
+
+    pub(crate) fn place(&mut self, pos: &Position, player: Player) {
+        self.board[pos.row][pos.col] = Some(player);
+        self.hash ^= transposition::stone_key(pos, player);
+        self.bits.place(pos, player);
+    }
+
+    pub(crate) fn remove(&mut self, pos: &Position) {
+        if let Some(player) = self.board[pos.row][pos.col].take() {
+            self.hash ^= transposition::stone_key(pos, player);
+            self.bits.remove(pos);
+        }
+    }
+
+    /// Records the result and puts both seats back to ready so the players can play again.
+    pub fn finish(&mut self, result: GameResult) {
+        self.result = Some(result);
+        self.status = GameStatus::Ready;
+        self.x_status = PlayerStatus::Ready;
+        self.o_status = PlayerStatus::Ready;
+        // The bot is always ready for another game.
+        match self.bot_player() {
+            Some(Player::X) => self.x_status = PlayerStatus::Confirmed,
+            Some(Player::O) => self.o_status = PlayerStatus::Confirmed,
+            None => {}
+        }
+    }
+
+    /// The random source for the bot's move at this point of the game, fixed by `seed` and
+    /// the number of moves played.
+    pub fn bot_rng(&self) -> ChaCha8Rng {
+        let ply = (self.moves.len() as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        ChaCha8Rng::seed_from_u64(self.seed as u64 ^ ply)
+    }
+
+    /// The colour the bot plays in a bot game. The bot sits at the nil user id.
+    pub fn bot_player(&self) -> Option<Player> {
+        match self.game_type {
+            GameType::Bot => self.player_of(Uuid::nil()),
+            GameType::Normal | GameType::Private => None,
+        }
+    }
+
+    pub fn set_seat(&mut self, player: Player, user_id: Uuid) {
+        match player {
+            Player::X => self.x = Some(user_id),
+            Player::O => self.o = Some(user_id),
+        }
+    }
+
+    /// The colour a user is seated at, if they are a player in this game.
+    pub fn player_of(&self, user_id: Uuid) -> Option<Player> {
+        if self.x == Some(user_id) {
+            Some(Player::X)
+        } else if self.o == Some(user_id) {
+            Some(Player::O)
+        } else {
+            None
+        }
+    }
+
+    pub fn set_opening(&mut self, opening: Opening) {
+        self.opening = opening;
+        self.opening_step = match opening {
+            Opening::Swap | Opening::Swap2 if self.moves.is_empty() => OpeningStep::Propose,
+            _ => OpeningStep::Done,
+        };
+    }
+
+    fn seat(&self, player: Player) -> Option<Uuid> {
+        match player {
+            Player::X => self.x,
+            Player::O => self.o,
+        }
+    }
+
+    /// The user allowed to act next. During a swap opening this is the opener or the
+    /// opponent regardless of which colour the next stone is; seats only change once a
+    /// colour has been chosen, so the opener is always whoever sits at `init_player`.
+    pub fn expected_actor(&self) -> Option<Uuid> {
+        match self.opening_step {
+            OpeningStep::Propose | OpeningStep::ChooseAfterExtend => self.seat(self.init_player),
+            OpeningStep::Choose | OpeningStep::Extend => self.seat(self.init_player.opponent()),
+            OpeningStep::Done => self.seat(self.next_player),
+        }
+    }
+
+    /// The chooser takes `player`'s stones. Picking the colour they are not currently seated
+    /// at swaps the seats.
+    pub fn choose_colour(&mut self, player: Player) -> Result<()> {
+        let chooser_colour = match self.opening_step {
+            OpeningStep::Choose => self.init_player.opponent(),
+            OpeningStep::ChooseAfterExtend => self.init_player,
+            _ => return Err(anyhow::anyhow!("Not choosing a colour")),
+        };
+        if player != chooser_colour {
+            std::mem::swap(&mut self.x, &mut self.o);
+            std::mem::swap(&mut self.x_status, &mut self.o_status);
+        }
+        self.opening_step = OpeningStep::Done;
+        Ok(())
+    }
+
+    /// Swap2: the opponent places two more stones instead of choosing a colour.
+    pub fn extend_opening(&mut self) -> Result<()> {
+        if self.opening != Opening::Swap2 || self.opening_step != OpeningStep::Choose {
+            return Err(anyhow::anyhow!("Can't place more stones now"));
+        }
+        self.opening_step = OpeningStep::Extend;
+        Ok(())
+    }
+
+    fn check_opening(&self, position: &Position) -> Result<()> {
+        if matches!(
+            self.opening_step,
+            OpeningStep::Choose | OpeningStep::ChooseAfterExtend
+        ) {
+            return Err(anyhow::anyhow!("Waiting for colour choice"));
+        }
+        if self.opening == Opening::Pro {
+            let center = (self.board_size / 2) as isize;
+            let distance = (position.row as isize - center)
+                .abs()
+                .max((position.col as isize - center).abs());
+            match self.moves.len() {
+                0 if distance != 0 => {
+                    return Err(anyhow::anyhow!("First move must be in the centre"))
+                }
+                2 if distance < 3 => {
+                    return Err(anyhow::anyhow!(
+                        "Third move must be at least three lines from the centre"
+                    ))
+                }
+                _ => {}
+            }
         }
+        Ok(())
     }
 
     pub fn play(&mut self, next_move @ Move { player, position }: &Move) -> Result<()> {
-        if self.winner.is_some() {
-            return Err(anyhow::anyhow!("Game already won"));
+        if self.winner.is_some() || self.result.is_some() {
+            return Err(anyhow::anyhow!("Game already ended"));
         }
         if self.x.is_none() || self.o.is_none() {
             return Err(anyhow::anyhow!("Game not started"));
@@ -84,7 +600,7 @@ impl Game {
         if self.next_player != *player {
             return Err(anyhow::anyhow!("Invalid player"));
         }
-        if position.col > BOARD_SIZE || position.row > BOARD_SIZE {
+        if position.col >= self.board_size || position.row >= self.board_size {
             return Err(anyhow::anyhow!("Invalid move"));
         }
         if self.board[position.row][position.col].is_some() {
@@ -96,31 +612,67 @@ impl Game {
                 return Err(anyhow::anyhow!("Invalid move"));
             }
         }
-        self.board[position.row][position.col] = Some(*player);
+        self.check_opening(position)?;
+        if let Some(reason) = self.forbidden_move(position, *player) {
+            return Err(reason.into());
+        }
+        self.place(position, *player);
         self.next_player = match player {
             Player::X => Player::O,
             Player::O => Player::X,
         };
         self.moves.push(*next_move);
+        self.opening_step = match (self.opening_step, self.moves.len()) {
+            (OpeningStep::Propose, 3) => OpeningStep::Choose,
+            (OpeningStep::Extend, 5) => OpeningStep::ChooseAfterExtend,
+            (step, _) => step,
+        };
         Ok(())
     }
 
-    pub fn find_bot_move(&mut self, depth: i32) -> Option<Position> {
-        let mut bot_score = -MAX_SCORE - 1;
-        let mut bot_moves = vec![];
+    /// Searches with iterative deepening until `settings.depth` or, unless `settings.fixed_depth`
+    /// is set, the time budget runs out, and plays the best move for the side to move from the deepest iteration that finished.
+    /// Setting `cancelled` stops the search early. `tt` may be carried over from earlier turns
+    /// of the same room, and is shared with the `settings.threads - 1` helper threads.
+    pub fn find_bot_move(
+        &mut self,
+        settings: &BotSettings,
+        cancelled: &AtomicBool,
+        tt: &TranspositionTable,
+    ) -> Option<Position> {
+        let bot = self.next_player;
+        let opponent = bot.opponent();
+        let limits = SearchLimits::new(settings, bot, cancelled);
+        let blunder_rate = settings.blunder_rate;
         if self.moves.is_empty() {
-            return Some(Position::new(BOARD_SIZE / 2, BOARD_SIZE / 2));
+            return Some(Position::new(self.board_size / 2, self.board_size / 2));
         }
-        let alpha = -MAX_SCORE;
-        let beta = MAX_SCORE;
-
-        let (_, mut threats) = self.evaluate();
+        let mut rng = self.bot_rng();
+        if rng.gen_bool(blunder_rate.clamp(0.0, 1.0)) {
+            let mut candidates = vec![];
+            for row in 0..self.board_size {
+                for col in 0..self.board_size {
+                    let pos = Position::new(col, row);
+                    if self.board[row][col].is_none()
+                        && self.is_near_existing_move(row, col)
+                        && self.is_legal(&pos, bot)
+                    {
+                        candidates.push(pos);
+                    }
+                }
+            }
+            if !candidates.is_empty() {
+                return Some(candidates[rng.gen_range(0..candidates.len())]);
+            }
+        }
+        let (_, mut threats) = self.evaluate(&limits);
+        threats.retain(|(_, pos, _)| self.is_legal(pos, bot));
 
         let mut player_threat = 0;
         let mut bot_threat = 0;
 
-        for row in 0..BOARD_SIZE {
-            for col in 0..BOARD_SIZE {
+        for row in 0..self.board_size {
+            for col in 0..self.board_size {
                 if self.board[row][col].is_some() {
                     continue;
                 }
@@ -130,17 +682,20 @@ impl Game {
                 }
 
                 let pos = Position::new(col, row);
-                self.board[row][col] = Some(Player::O);
+                if !self.is_legal(&pos, bot) {
+                    continue;
+                }
+                self.place(&pos, bot);
                 if self.check_winning_move(&pos).unwrap().is_some() {
-                    self.board[row][col] = None;
+                    self.remove(&pos);
                     return Some(pos);
                 }
-                self.board[row][col] = None;
+                self.remove(&pos);
             }
         }
 
-        for row in 0..BOARD_SIZE {
-            for col in 0..BOARD_SIZE {
+        for row in 0..self.board_size {
+            for col in 0..self.board_size {
                 if self.board[row][col].is_some() {
                     continue;
                 }
@@ -148,35 +703,43 @@ impl Game {
                     continue;
                 }
                 let pos = Position::new(col, row);
-                self.board[row][col] = Some(Player::X);
+                if !self.is_legal(&pos, opponent) {
+                    continue;
+                }
+                self.place(&pos, opponent);
                 if self.check_winning_move(&pos).unwrap().is_some() {
-                    self.board[row][col] = None;
+                    self.remove(&pos);
                     return Some(pos);
                 }
-                self.board[row][col] = None;
+                self.remove(&pos);
             }
         }
 
-        threats.iter().for_each(|threat| match threat.2 {
-            Player::X => player_threat = player_threat.max(threat.0),
-            Player::O => bot_threat = bot_threat.max(threat.0),
+        threats.iter().for_each(|threat| {
+            if threat.2 == bot {
+                bot_threat = bot_threat.max(threat.0);
+            } else {
+                player_threat = player_threat.max(threat.0);
+            }
         });
 
         for threat in threats.iter() {
-            if threat.2 == Player::O && threat.0 == 4 {
+            if threat.0 == 4 {
                 return Some(threat.1);
             }
-            if threat.2 == Player::X && threat.0 == 4 {
-                return Some(threat.1);
-            }
-            match threat.2 {
-                Player::O => player_threat = player_threat.max(threat.0),
-                Player::X => bot_threat = bot_threat.max(threat.0),
+            if threat.2 == bot {
+                player_threat = player_threat.max(threat.0);
+            } else {
+                bot_threat = bot_threat.max(threat.0);
             }
         }
 
-        for row in 0..BOARD_SIZE {
-            for col in 0..BOARD_SIZE {
+        if let Some(pos) = solver::threat_move(self, bot, settings, &limits) {
+            return Some(pos);
+        }
+
+        for row in 0..self.board_size {
+            for col in 0..self.board_size {
                 if self.board[row][col].is_some() {
                     continue;
                 }
@@ -184,24 +747,23 @@ impl Game {
                 if !self.is_near_existing_move(row, col) {
                     continue;
                 }
-                self.board[row][col] = Some(Player::O);
-                let count = self.double_winning_threats(
-                    row,
-                    col,
-                    &[
-                        "_oooo_", "_oooox", "xoooo_", "oo_oo", "o_ooo", "ooo_o", "_ooo_", "_oo_o_",
-                        "_o_oo_",
-                    ],
-                );
-                self.board[row][col] = None;
+                let pos = Position::new(col, row);
+                if !self.is_legal(&pos, bot) {
+                    continue;
+                }
+                self.place(&pos, bot);
+                let count = self
+                    .bits
+                    .winning_threat(&pos, bot, limits.profile.winning_threats());
+                self.remove(&pos);
                 if count > player_threat {
                     return Some(Position::new(col, row));
                 }
             }
         }
 
-        for row in 0..BOARD_SIZE {
-            for col in 0..BOARD_SIZE {
+        for row in 0..self.board_size {
+            for col in 0..self.board_size {
                 if self.board[row][col].is_some() {
                     continue;
                 }
@@ -209,16 +771,15 @@ impl Game {
                 if !self.is_near_existing_move(row, col) {
                     continue;
                 }
-                self.board[row][col] = Some(Player::O);
-                let count = self.double_winning_threats(
-                    row,
-                    col,
-                    &[
-                        "_xxxx_", "_xxxxo", "oxxxx_", "xx_xx", "x_xxx", "xxx_x", "_xxx_", "_x_xx_",
-                        "_xx_x_",
-                    ],
-                );
-                self.board[row][col] = None;
+                let pos = Position::new(col, row);
+                if !self.is_legal(&pos, bot) {
+                    continue;
+                }
+                self.place(&pos, bot);
+                let count =
+                    self.bits
+                        .winning_threat(&pos, opponent, limits.profile.winning_threats());
+                self.remove(&pos);
                 if count > bot_threat {
                     return Some(Position::new(col, row));
                 }
@@ -228,7 +789,7 @@ impl Game {
         if threats.is_empty() {
             let last_move = self.moves.last().unwrap().position;
             let neighbors = self.get_neighbors(last_move.row, last_move.col);
-            threats.extend(neighbors.into_iter().map(|pos| (0, pos, Player::O)));
+            threats.extend(neighbors.into_iter().map(|pos| (0, pos, bot)));
             if self.moves.len() > 1 {
                 let last_move = self.moves[self.moves.len() - 2].position;
                 let neighbors = self.get_neighbors(last_move.row, last_move.col);
@@ -249,8 +810,8 @@ impl Game {
 
         if threats.is_empty() {
             let mut neighbors = vec![];
-            for row in 0..BOARD_SIZE {
-                for col in 0..BOARD_SIZE {
+            for row in 0..self.board_size {
+                for col in 0..self.board_size {
                     if self.board[row][col].is_some() {
                         continue;
                     }
@@ -259,31 +820,40 @@ impl Game {
                         continue;
                     }
                     let pos = Position::new(col, row);
-                    neighbors.push((0, pos, Player::O));
+                    neighbors.push((0, pos, bot));
                 }
             }
             threats.extend(neighbors);
         }
-
-        for (_, pos @ Position { row, col }, _) in threats {
-            self.next_player = Player::X;
-            self.board[row][col] = Some(Player::O);
-            let score = self.minimax(depth, false, alpha, beta, &mut HashMap::new());
-
-            self.next_player = Player::O;
-
-            self.board[row][col] = None;
-            match score.cmp(&bot_score) {
-                Ordering::Greater => {
-                    bot_score = score;
-                    bot_moves = vec![pos];
-                }
-                Ordering::Equal => {
-                    bot_moves.push(pos);
-                }
-                Ordering::Less => {}
+        threats.retain(|(_, pos, _)| self.is_legal(pos, bot));
+        threats.truncate(limits.max_candidates);
+
+        let candidates: Vec<Position> = threats.iter().map(|(_, pos, _)| *pos).collect();
+        // Lazy SMP: helpers search the same root in other orders and only share what they
+        // find through `tt`; the move comes from this thread's search.
+        let helpers_done = AtomicBool::new(false);
+        let threads = if settings.fixed_depth {
+            1
+        } else {
+            settings.threads
+        };
+        let mut bot_moves = std::thread::scope(|scope| {
+            for helper in 1..threads {
+                let mut game = self.clone();
+                let shift = helper % candidates.len().max(1);
+                let mut candidates = candidates.clone();
+                candidates.rotate_left(shift);
+                let helpers_done = &helpers_done;
+                scope.spawn(move || {
+                    let limits = SearchLimits::new(settings, bot, helpers_done);
+                    let first_depth = (helper % 2) as i32;
+                    game.search_root(&candidates, first_depth..=settings.depth, &limits, tt);
+                });
             }
-        }
+            let moves = self.search_root(&candidates, 0..=settings.depth, &limits, tt);
+            helpers_done.store(true, AtomicOrdering::Relaxed);
+            moves
+        });
 
         bot_moves.sort_by(|a, b| {
             match self
@@ -292,7 +862,7 @@ impl Game {
             {
                 Ordering::Less => Ordering::Less,
                 Ordering::Greater => Ordering::Greater,
-                Ordering::Equal => match rand::thread_rng().gen_range(0..=2) {
+                Ordering::Equal => match rng.gen_range(0..=2) {
                     0 => Ordering::Less,
                     1 => Ordering::Equal,
                     _ => Ordering::Greater,
@@ -302,339 +872,413 @@ impl Game {
         bot_moves.last().cloned()
     }
 
-    fn board_to_string(&self) -> String {
-        self.board
-            .iter()
-            .map(|row| {
-                row.iter()
-                    .map(|cell| match cell {
-                        Some(p) => match p {
-                            Player::O => "o",
-                            Player::X => "x",
-                        },
-                        None => "_",
-                    })
-                    .collect::<String>()
-            })
-            .collect::<Vec<String>>()
-            .join("")
+    /// The `count` best moves for the side to move with their scores, from the deepest search
+    /// that finished. Scores are from the mover's point of view, best first.
+    pub fn rank_moves(
+        &mut self,
+        count: usize,
+        settings: &BotSettings,
+        cancelled: &AtomicBool,
+        tt: &TranspositionTable,
+    ) -> Vec<Candidate> {
+        let mover = self.next_player;
+        if self.moves.is_empty() {
+            let centre = Position::new(self.board_size / 2, self.board_size / 2);
+            return vec![Candidate {
+                position: centre,
+                score: 0,
+            }];
+        }
+        let limits = SearchLimits::new(settings, mover, cancelled);
+        let mut candidates = self.root_candidates(&limits);
+        candidates.truncate(limits.max_candidates.max(count));
+
+        let mut ranked = vec![];
+        for depth in 0..=settings.depth {
+            match self.score_root(&candidates, depth, &limits, tt) {
+                Some(scored) => ranked = scored,
+                None => break,
+            }
+        }
+        ranked.sort_by_key(|candidate| std::cmp::Reverse(candidate.score));
+        ranked.truncate(count);
+        ranked
     }
 
-    fn check_winning_horizontal(
-        &self,
-        Position { row, col }: &Position,
-    ) -> Result<Option<Vec<Move>>> {
-        if *col >= BOARD_SIZE || *row >= BOARD_SIZE {
-            return Err(anyhow::anyhow!("Invalid position"));
+    /// The engine's best move next to `played`, both scored for the side to move by the
+    /// deepest search that finished. Used to grade moves after a game.
+    pub fn compare_move(
+        &mut self,
+        played: Position,
+        settings: &BotSettings,
+        cancelled: &AtomicBool,
+        tt: &TranspositionTable,
+    ) -> (Candidate, Candidate) {
+        let limits = SearchLimits::new(settings, self.next_player, cancelled);
+        let mut candidates = self.root_candidates(&limits);
+        candidates.truncate(limits.max_candidates);
+        if !candidates.contains(&played) {
+            candidates.push(played);
         }
-
-        if self.board[*row][*col].is_none() {
-            return Err(anyhow::anyhow!("Position is empty"));
+        let mut scored = vec![];
+        for depth in 0..=settings.depth {
+            match self.score_root(&candidates, depth, &limits, tt) {
+                Some(scores) => scored = scores,
+                None => break,
+            }
         }
-
-        let cell = self.board[*row][*col].unwrap();
-        let mut winning_count = 0;
-        let mut moves = vec![];
-        for i in col.saturating_sub(WINNING_MOVE_COUNT)
-            ..col.saturating_add(WINNING_MOVE_COUNT).min(BOARD_SIZE)
-        {
-            if let Some(c) = self.board[*row][i] {
-                if c == cell {
-                    winning_count += 1;
-                    moves.push(Move::new(cell, Position::new(i, *row)));
-                    if winning_count >= WINNING_MOVE_COUNT {
-                        return Ok(Some(moves));
-                    }
+        let played = scored
+            .iter()
+            .find(|candidate| candidate.position == played)
+            .copied()
+            .unwrap_or(Candidate {
+                position: played,
+                score: 0,
+            });
+        let best = scored
+            .into_iter()
+            .reduce(|best, candidate| {
+                if candidate.score > best.score {
+                    candidate
                 } else {
-                    winning_count = 0;
-                    moves = vec![];
+                    best
                 }
-            } else {
-                winning_count = 0;
-                moves = vec![];
+            })
+            .unwrap_or(played);
+        (best, played)
+    }
+
+    /// Legal moves near the stones for the side to move, those on threats first.
+    fn root_candidates(&mut self, limits: &SearchLimits) -> Vec<Position> {
+        let mover = self.next_player;
+        let (_, threats) = self.evaluate(limits);
+        let nearby: Vec<Position> = (0..self.board_size)
+            .flat_map(|row| (0..self.board_size).map(move |col| Position::new(col, row)))
+            .filter(|pos| self.is_near_existing_move(pos.row, pos.col))
+            .collect();
+        let mut candidates: Vec<Position> = vec![];
+        for pos in threats.iter().map(|(_, pos, _)| *pos).chain(nearby) {
+            if self.board[pos.row][pos.col].is_none()
+                && !candidates.contains(&pos)
+                && self.is_legal(&pos, mover)
+            {
+                candidates.push(pos);
             }
         }
-
-        Ok(None)
+        candidates
     }
 
-    fn patterns(&self, str: &str, pattern: &str) -> usize {
-        let o = pattern.chars().filter(|c| *c == 'o').count();
-        let x = pattern.chars().filter(|c| *c == 'x').count();
-        let chr = if o > x { 'o' } else { 'x' };
-        if str.contains(pattern) {
-            return pattern.chars().filter(|c| *c == chr).count();
+    /// Iterative deepening over the root `candidates`. Returns the best moves of the deepest
+    /// iteration that finished.
+    fn search_root(
+        &mut self,
+        candidates: &[Position],
+        depths: RangeInclusive<i32>,
+        limits: &SearchLimits,
+        tt: &TranspositionTable,
+    ) -> Vec<Position> {
+        let mut bot_moves = vec![];
+        for depth in depths {
+            let Some(scored) = self.score_root(candidates, depth, limits, tt) else {
+                break;
+            };
+            let best = scored.iter().map(|candidate| candidate.score).max();
+            bot_moves = scored
+                .into_iter()
+                .filter(|candidate| Some(candidate.score) == best)
+                .map(|candidate| candidate.position)
+                .collect();
         }
-        0
+        bot_moves
     }
 
-    fn double_winning_threats(&self, row: usize, col: usize, patterns: &[&str]) -> usize {
-        let mut threats = 0;
-        let mut count_row = 0;
-        let mut count_col = 0;
-        let mut count_upper_diagonal = 0;
-        let mut count_lower_diagonal = 0;
-        patterns.iter().for_each(|pattern| {
-            count_row = count_row.max(self.patterns(&moves_to_string(&self.board[row]), pattern));
-            let column: Vec<Option<Player>> = self.board.iter().map(|row| row[col]).collect();
-            count_col = count_col.max(self.patterns(&moves_to_string(&column), pattern));
-
-            let diagonal = self.get_diagonal(row, col, true);
-            count_upper_diagonal =
-                count_upper_diagonal.max(self.patterns(&moves_to_string(&diagonal), pattern));
+    /// Scores each of `candidates` for the side to move with a search `depth` plies deep, or
+    /// `None` if the search was stopped before it finished.
+    fn score_root(
+        &mut self,
+        candidates: &[Position],
+        depth: i32,
+        limits: &SearchLimits,
+        tt: &TranspositionTable,
+    ) -> Option<Vec<Candidate>> {
+        let bot = self.next_player;
+        let opponent = bot.opponent();
+        let mut scored = Vec::with_capacity(candidates.len());
+        for &pos in candidates {
+            self.next_player = opponent;
+            self.place(&pos, bot);
+            // Scores are from O's point of view; flip them when the bot plays X.
+            let score = self.minimax(
+                depth,
+                opponent == Player::O,
+                -MAX_SCORE,
+                MAX_SCORE,
+                limits,
+                tt,
+            );
+            let score = if bot == Player::O { score } else { -score };
 
-            let diagonal = self.get_diagonal(row, col, false);
-            count_lower_diagonal =
-                count_lower_diagonal.max(self.patterns(&moves_to_string(&diagonal), pattern));
-        });
-        threats += if count_row > 0 { 1 } else { 0 };
-        threats += if count_col > 0 { 1 } else { 0 };
-        threats += if count_lower_diagonal > 0 { 1 } else { 0 };
-        threats += if count_upper_diagonal > 0 { 1 } else { 0 };
-
-        if threats >= 2 {
-            count_row
-                .max(count_col)
-                .max(count_lower_diagonal)
-                .max(count_upper_diagonal)
-        } else {
-            0
-        }
-    }
+            self.next_player = bot;
 
-    fn find_patterns(
-        &self,
-        vect_str: &str,
-        pattern: &str,
-        bot_scores: &mut Vec<i32>,
-        player_scores: &mut Vec<i32>,
-        threats: &mut Vec<(usize, Position, Player)>,
-        positions: &[Position],
-    ) {
-        let o = pattern.chars().filter(|c| *c == 'o').count();
-        let x = pattern.chars().filter(|c| *c == 'x').count();
-        let score = (o.max(x) as i32) * 100;
-        let player = if o > x { Player::O } else { Player::X };
-
-        let pos = pattern
-            .chars()
-            .enumerate()
-            .filter_map(|(i, c)| if c == '_' { Some(i) } else { None });
-        if let Some(idx) = vect_str.find(pattern) {
-            pos.for_each(|p| {
-                threats.push((
-                    match player {
-                        Player::O => o,
-                        Player::X => x,
-                    },
-                    positions[idx + p],
-                    player,
-                ))
-            });
-            match player {
-                Player::O => bot_scores.push(score),
-                Player::X => player_scores.push(score),
+            self.remove(&pos);
+            if depth > 0 && limits.is_stopped() {
+                return None;
             }
+            scored.push(Candidate {
+                position: pos,
+                score,
+            });
         }
+        Some(scored)
     }
 
     fn find_threats(
         &self,
-        vect_str: &str,
+        line: usize,
+        limits: &SearchLimits,
         player_scores: &mut Vec<i32>,
         bot_scores: &mut Vec<i32>,
-        positions: Vec<Position>,
     ) -> Vec<(usize, Position, Player)> {
         let mut threats = vec![];
-        if vect_str.contains("ooooo") {
-            bot_scores.push(MAX_SCORE);
-            return threats;
-        }
-        if vect_str.contains("xxxxx") {
-            player_scores.push(MAX_SCORE);
-            return threats;
-        }
-
-        for pattern in [
-            "_oooo_", "_oooox", "xoooo_", "oo_oo", "o_ooo", "ooo_o", "_ooo_", "_oo_o_", "_o_oo_",
-            "_oo_o_", "_o_oo_", "_xxxx_", "_xxxxo", "oxxxx_", "xx_xx", "x_xxx", "xxx_x", "_xxx_",
-            "_xx_x_", "_x_xx_", "_xx_x_", "_x_xx_",
-        ] {
-            self.find_patterns(
-                vect_str,
-                pattern,
-                bot_scores,
-                player_scores,
-                &mut threats,
-                &positions,
-            );
+        for player in [Player::O, Player::X] {
+            if self.bits.has_five(line, player, |len, blocked| {
+                self.is_five(len, blocked, player)
+            }) {
+                match player {
+                    Player::O => bot_scores.push(MAX_SCORE),
+                    Player::X => player_scores.push(MAX_SCORE),
+                }
+                return threats;
+            }
+        }
+
+        for (pattern, offset) in self.bits.threats(line) {
+            let cells = pattern.empty_cells();
+            for cell in cells.iter().cycle().take(cells.len() * pattern.count) {
+                threats.push((
+                    pattern.stones,
+                    self.bits.position(line, offset + cell),
+                    pattern.player,
+                ));
+            }
+            // Anything at MAX_SCORE would read as a five.
+            let score = limits
+                .profile
+                .weigh(pattern.score, pattern.player, limits.bot)
+                .round()
+                .clamp(f64::from(1 - MAX_SCORE), f64::from(MAX_SCORE - 1))
+                as i32;
+            let scores = match pattern.player {
+                Player::O => &mut *bot_scores,
+                Player::X => &mut *player_scores,
+            };
+            scores.extend(std::iter::repeat_n(score, pattern.count));
         }
 
         threats.sort_by(|a, b| a.0.cmp(&b.0).reverse());
         threats
     }
 
-    fn check_winning_vertical(
-        &self,
-        Position { row, col }: &Position,
-    ) -> Result<Option<Vec<Move>>> {
-        if *col >= BOARD_SIZE || *row >= BOARD_SIZE {
+    pub fn check_winning_move(&self, pos: &Position) -> Result<Option<Vec<Move>>> {
+        if pos.col >= self.board_size || pos.row >= self.board_size {
             return Err(anyhow::anyhow!("Invalid position"));
         }
-
-        if self.board[*row][*col].is_none() {
+        let Some(player) = self.board[pos.row][pos.col] else {
             return Err(anyhow::anyhow!("Position is empty"));
-        }
-
-        let cell = self.board[*row][*col].unwrap();
-        let mut winning_count = 0;
-        let mut moves = vec![];
-        for i in row.saturating_sub(WINNING_MOVE_COUNT)
-            ..row.saturating_add(WINNING_MOVE_COUNT).min(BOARD_SIZE)
-        {
-            if let Some(c) = self.board[i][*col] {
-                if c == cell {
-                    winning_count += 1;
-                    moves.push(Move::new(cell, Position::new(*col, i)));
-                    if winning_count >= WINNING_MOVE_COUNT {
-                        return Ok(Some(moves));
-                    }
-                } else {
-                    winning_count = 0;
-                    moves = vec![];
+        };
+        for (direction, (line, bit)) in self.bits.lines_through(pos).into_iter().enumerate() {
+            let (start, end, blocked) = self.bits.run_through(line, bit, player);
+            if self.is_five(end - start + 1, blocked, player) {
+                let mut run: Vec<Move> = (start..=end)
+                    .map(|bit| Move::new(player, self.bits.position(line, bit)))
+                    .collect();
+                // Winning runs are listed top to bottom; up-right diagonals run bottom to top.
+                if DIRECTIONS[direction] == (1, -1) {
+                    run.reverse();
                 }
-            } else {
-                winning_count = 0;
-                moves = vec![];
+                return Ok(Some(run));
             }
         }
-
         Ok(None)
     }
 
-    fn get_diagonal(&self, row: usize, col: usize, is_upper_left: bool) -> Vec<Option<Player>> {
-        let mut diagonal = vec![];
-        for i in -(WINNING_MOVE_COUNT as i32)..(WINNING_MOVE_COUNT as i32) + 1 {
-            let new_row = (row as i32) + i;
-            let new_col = (col as i32)
-                + match is_upper_left {
-                    true => i,
-                    false => -i,
-                };
-            if new_row < 0
-                || new_row >= BOARD_SIZE as i32
-                || new_col < 0
-                || new_col >= BOARD_SIZE as i32
-            {
-                continue;
-            }
-            diagonal.push(self.board[new_row as usize][new_col as usize]);
+    /// Whether `player` moves first, i.e. plays black.
+    fn is_black(&self, player: Player) -> bool {
+        player == self.init_player
+    }
+
+    /// Whether a run of `len` stones counts as a win under the game's rule set.
+    /// `blocked` is true when both ends of the run are capped by opponent stones.
+    fn is_five(&self, len: usize, blocked: bool, player: Player) -> bool {
+        match self.rule_set {
+            RuleSet::Freestyle => len >= WINNING_MOVE_COUNT,
+            RuleSet::Standard => len == WINNING_MOVE_COUNT,
+            RuleSet::Renju if self.is_black(player) => len == WINNING_MOVE_COUNT,
+            RuleSet::Renju => len >= WINNING_MOVE_COUNT,
+            RuleSet::Caro => len >= WINNING_MOVE_COUNT && !blocked,
         }
-        diagonal
     }
 
-    fn check_winning_diagonal(
+    fn cell(&self, row: isize, col: isize) -> Option<Option<Player>> {
+        if row < 0 || col < 0 || row >= self.board_size as isize || col >= self.board_size as isize
+        {
+            return None;
+        }
+        Some(self.board[row as usize][col as usize])
+    }
+
+    /// Contiguous stones of `player` through `pos` along `direction`, and whether the run is
+    /// capped by opponent stones at both ends. The board edge does not count as a block.
+    fn run_through(
         &self,
-        Position { row, col }: &Position,
-        is_upper_left: bool,
-    ) -> Result<Option<Vec<Move>>> {
-        if *col >= BOARD_SIZE || *row >= BOARD_SIZE {
-            return Err(anyhow::anyhow!("Invalid position"));
+        pos: &Position,
+        (dr, dc): (isize, isize),
+        player: Player,
+    ) -> (Vec<Position>, bool) {
+        let (row, col) = (pos.row as isize, pos.col as isize);
+        let mut start = 0;
+        while self.cell(row + (start - 1) * dr, col + (start - 1) * dc) == Some(Some(player)) {
+            start -= 1;
+        }
+        let mut end = 0;
+        while self.cell(row + (end + 1) * dr, col + (end + 1) * dc) == Some(Some(player)) {
+            end += 1;
         }
+        let opponent = Some(Some(player.opponent()));
+        let blocked = self.cell(row + (start - 1) * dr, col + (start - 1) * dc) == opponent
+            && self.cell(row + (end + 1) * dr, col + (end + 1) * dc) == opponent;
+        let run = (start..=end)
+            .map(|i| Position::new((col + i * dc) as usize, (row + i * dr) as usize))
+            .collect();
+        (run, blocked)
+    }
 
-        if self.board[*row][*col].is_none() {
-            return Err(anyhow::anyhow!("Position is empty"));
+    /// Returns why placing `player` at the empty `pos` is forbidden, if it is. Only black is
+    /// ever restricted, and only under Renju rules; a move that makes exactly five is always
+    /// allowed.
+    pub fn forbidden_move(&mut self, pos: &Position, player: Player) -> Option<ForbiddenMove> {
+        if self.rule_set != RuleSet::Renju || !self.is_black(player) {
+            return None;
         }
+        self.forbidden_at(pos, player, RENJU_THREE_DEPTH)
+    }
 
-        let cell = self.board[*row][*col].unwrap();
-        let mut winning_count = 0;
-        let mut moves = vec![];
+    fn forbidden_at(
+        &mut self,
+        pos: &Position,
+        player: Player,
+        depth: usize,
+    ) -> Option<ForbiddenMove> {
+        if self.board[pos.row][pos.col].is_some() {
+            return None;
+        }
+        self.place(pos, player);
+        let runs: Vec<usize> = DIRECTIONS
+            .iter()
+            .map(|direction| self.run_through(pos, *direction, player).0.len())
+            .collect();
+        let forbidden = if runs.contains(&WINNING_MOVE_COUNT) {
+            None
+        } else if runs.iter().any(|len| *len > WINNING_MOVE_COUNT) {
+            Some(ForbiddenMove::Overline)
+        } else if DIRECTIONS
+            .iter()
+            .map(|direction| self.count_fours(pos, *direction, player))
+            .sum::<usize>()
+            >= 2
+        {
+            Some(ForbiddenMove::DoubleFour)
+        } else if DIRECTIONS
+            .iter()
+            .filter(|direction| self.is_open_three(pos, **direction, player, depth))
+            .count()
+            >= 2
+        {
+            Some(ForbiddenMove::DoubleThree)
+        } else {
+            None
+        };
+        self.remove(pos);
+        forbidden
+    }
 
-        for i in -(WINNING_MOVE_COUNT as i32)..(WINNING_MOVE_COUNT as i32) + 1 {
-            if (*row as i32) + i < 0 {
+    /// Offsets along `direction` of the empty cells that would complete exactly five through
+    /// `pos`.
+    fn five_points(
+        &mut self,
+        pos: &Position,
+        (dr, dc): (isize, isize),
+        player: Player,
+    ) -> Vec<isize> {
+        let (row, col) = (pos.row as isize, pos.col as isize);
+        let reach = WINNING_MOVE_COUNT as isize - 1;
+        let mut points = vec![];
+        for i in -reach..=reach {
+            let (r, c) = (row + i * dr, col + i * dc);
+            if i == 0 || self.cell(r, c) != Some(None) {
                 continue;
             }
-            if (*col as i32)
-                + match is_upper_left {
-                    true => i,
-                    false => -i,
-                }
-                < 0
-            {
-                continue;
+            let point = Position::new(c as usize, r as usize);
+            self.place(&point, player);
+            let (run, _) = self.run_through(&point, (dr, dc), player);
+            let contains_pos = run.iter().any(|p| p.row == pos.row && p.col == pos.col);
+            if contains_pos && run.len() == WINNING_MOVE_COUNT {
+                points.push(i);
             }
+            self.remove(&point);
+        }
+        points
+    }
 
-            if (*row as i32) + i >= BOARD_SIZE as i32 {
-                continue;
-            }
-            if (*col as i32)
-                + match is_upper_left {
-                    true => i,
-                    false => -i,
-                }
-                >= BOARD_SIZE as i32
-            {
+    fn count_fours(&mut self, pos: &Position, direction: (isize, isize), player: Player) -> usize {
+        let points = self.five_points(pos, direction, player);
+        if points.len() == 2 && points[1] - points[0] == WINNING_MOVE_COUNT as isize {
+            // A straight four, `_xxxx_`, is a single four with two ways to finish it.
+            1
+        } else {
+            points.len()
+        }
+    }
+
+    /// A three is open when one more stone on the line makes a straight four, and that stone
+    /// is not itself forbidden.
+    fn is_open_three(
+        &mut self,
+        pos: &Position,
+        (dr, dc): (isize, isize),
+        player: Player,
+        depth: usize,
+    ) -> bool {
+        let (row, col) = (pos.row as isize, pos.col as isize);
+        let reach = WINNING_MOVE_COUNT as isize - 1;
+        for i in -reach..=reach {
+            let (r, c) = (row + i * dr, col + i * dc);
+            if i == 0 || self.cell(r, c) != Some(None) {
                 continue;
             }
-
-            if let Some(c) = self.board[(*row as i32 + i) as usize][(*col as i32
-                + match is_upper_left {
-                    true => i,
-                    false => -i,
-                }) as usize]
+            let point = Position::new(c as usize, r as usize);
+            self.place(&point, player);
+            let points = self.five_points(pos, (dr, dc), player);
+            self.remove(&point);
+            let straight_four =
+                points.len() == 2 && points[1] - points[0] == WINNING_MOVE_COUNT as isize;
+            if straight_four
+                && (depth == 0 || self.forbidden_at(&point, player, depth - 1).is_none())
             {
-                if c == cell {
-                    winning_count += 1;
-                    moves.push(Move::new(
-                        cell,
-                        Position::new(
-                            (*col as i32
-                                + match is_upper_left {
-                                    true => i,
-                                    false => -i,
-                                }) as usize,
-                            (*row as i32 + i) as usize,
-                        ),
-                    ));
-                    if winning_count >= WINNING_MOVE_COUNT {
-                        return Ok(Some(moves));
-                    }
-                } else {
-                    winning_count = 0;
-                    moves = vec![];
-                }
-            } else {
-                winning_count = 0;
-                moves = vec![];
+                return true;
             }
         }
-
-        Ok(None)
+        false
     }
 
-    pub fn check_winning_move(&self, pos: &Position) -> Result<Option<Vec<Move>>> {
-        let vertical = self.check_winning_vertical(pos)?;
-        if vertical.is_some() {
-            return Ok(vertical);
-        }
-        let horizontal = self.check_winning_horizontal(pos)?;
-        if horizontal.is_some() {
-            return Ok(horizontal);
-        }
-        let upper = self.check_winning_diagonal(pos, true)?;
-        if upper.is_some() {
-            return Ok(upper);
-        }
-        let lower = self.check_winning_diagonal(pos, false)?;
-        if lower.is_some() {
-            return Ok(lower);
-        }
-        Ok(None)
+    /// Whether `player` may legally place a stone at the empty `pos`.
+    pub(crate) fn is_legal(&mut self, pos: &Position, player: Player) -> bool {
+        self.forbidden_move(pos, player).is_none()
     }
 
     pub fn check_win(&self) -> Option<Vec<Move>> {
-        for row in 0..BOARD_SIZE {
-            for col in 0..BOARD_SIZE {
+        for row in 0..self.board_size {
+            for col in 0..self.board_size {
                 let pos = Position::new(col, row);
                 if let Ok(check) = self.check_winning_move(&pos) {
                     if check.is_some() {
@@ -646,106 +1290,21 @@ impl Game {
         None
     }
 
-    fn evaluate(&self) -> (i32, Vec<(usize, Position, Player)>) {
+    fn evaluate(&mut self, limits: &SearchLimits) -> (i32, Vec<(usize, Position, Player)>) {
+        self.bits.refresh(limits.profile.compiled());
         let mut player = vec![];
         let mut bot = vec![];
         let mut threats = vec![];
-        for row in 0..BOARD_SIZE {
-            let row_str = moves_to_string(&self.board[row]);
-            threats.extend(
-                self.find_threats(
-                    &row_str,
-                    &mut player,
-                    &mut bot,
-                    self.board[row]
-                        .iter()
-                        .enumerate()
-                        .map(|(i, _)| Position::new(i, row))
-                        .collect(),
-                ),
-            );
-        }
-
-        for col in 0..BOARD_SIZE {
-            let col_moves = self.board.iter().map(|row| row[col]).collect::<Vec<_>>();
-            threats.extend(
-                self.find_threats(
-                    &moves_to_string(&col_moves),
-                    &mut player,
-                    &mut bot,
-                    col_moves
-                        .iter()
-                        .enumerate()
-                        .map(|(i, _)| Position::new(col, i))
-                        .collect(),
-                ),
-            );
+        for line in self.bits.scan_order() {
+            threats.extend(self.find_threats(line, limits, &mut player, &mut bot));
         }
 
-        for row_start in 0..BOARD_SIZE {
-            let mut diagonal = vec![];
-            let mut positions = vec![];
-            for i in 0..BOARD_SIZE - row_start {
-                diagonal.push(self.board[row_start + i][i]);
-                positions.push(Position::new(i, row_start + i));
-            }
-            if diagonal.len() >= 5 {
-                let str = moves_to_string(&diagonal);
-                threats.extend(self.find_threats(&str, &mut player, &mut bot, positions));
-            }
-        }
-
-        for col_start in 1..BOARD_SIZE {
-            let mut diagonal = vec![];
-            let mut positions = vec![];
-
-            for i in 0..BOARD_SIZE - col_start {
-                diagonal.push(self.board[i][col_start + i]);
-                positions.push(Position::new(col_start + i, i));
-            }
-            if diagonal.len() >= 5 {
-                let str = moves_to_string(&diagonal);
-                threats.extend(self.find_threats(&str, &mut player, &mut bot, positions));
-            }
-        }
-
-        for col_start in 0..BOARD_SIZE {
-            let mut diagonal = vec![];
-            let mut positions = vec![];
-
-            for i in 0..BOARD_SIZE - col_start {
-                diagonal.push(self.board[BOARD_SIZE - 1 - i][col_start + i]);
-                positions.push(Position::new(col_start + i, BOARD_SIZE - 1 - i));
-            }
-            if diagonal.len() >= 5 {
-                let str = moves_to_string(&diagonal);
-                threats.extend(self.find_threats(&str, &mut player, &mut bot, positions));
-            }
-        }
-
-        for row_start in (0..BOARD_SIZE - 1).rev() {
-            let mut diagonal = vec![];
-            let mut positions = vec![];
-            for i in 0..row_start + 1 {
-                diagonal.push(self.board[row_start - i][i]);
-                positions.push(Position::new(i, row_start - i));
-            }
-            if diagonal.len() >= 5 {
-                let str = moves_to_string(&diagonal);
-                threats.extend(self.find_threats(&str, &mut player, &mut bot, positions));
-            }
-        }
-
-        threats.sort_by(|a, b| match a.0.cmp(&b.0) {
-            Ordering::Equal => {
-                if matches!(a.2, Player::O) {
-                    Ordering::Greater
-                } else {
-                    Ordering::Less
-                }
-            }
-            Ordering::Greater => Ordering::Less,
-            Ordering::Less => Ordering::Greater,
+        // On equal sizes the side to move comes first: it can complete its own threats before
+        // it has to answer the opponent's.
+        let mover = self.next_player;
+        threats.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| (a.2 != mover).cmp(&(b.2 != mover)))
         });
 
         (
@@ -753,8 +1312,13 @@ impl Game {
                 (Some(p), None) => *p * -1,
                 (None, Some(b)) => *b,
                 (Some(p), Some(b)) => match p.cmp(b) {
-                    Ordering::Greater | Ordering::Equal => *p * -1,
+                    Ordering::Greater => *p * -1,
                     Ordering::Less => *b,
+                    // Equal shapes favour the side to move, who gets to use theirs first.
+                    Ordering::Equal => match mover {
+                        Player::O => *b,
+                        Player::X => *p * -1,
+                    },
                 },
                 _ => 0,
             },
@@ -776,9 +1340,9 @@ impl Game {
                 let new_row = row as isize + dy;
                 let new_col = col as isize + dx;
                 if new_row >= 0
-                    && new_row < BOARD_SIZE as isize
+                    && new_row < self.board_size as isize
                     && new_col >= 0
-                    && new_col < BOARD_SIZE as isize
+                    && new_col < self.board_size as isize
                     && self.board[new_row as usize][new_col as usize].is_some()
                     && self.board[new_row as usize][new_col as usize].unwrap() == self.next_player
                 {
@@ -797,9 +1361,9 @@ impl Game {
                 let new_row = row as isize + dy;
                 let new_col = col as isize + dx;
                 if new_row >= 0
-                    && new_row < BOARD_SIZE as isize
+                    && new_row < self.board_size as isize
                     && new_col >= 0
-                    && new_col < BOARD_SIZE as isize
+                    && new_col < self.board_size as isize
                     && self.board[new_row as usize][new_col as usize].is_none()
                 {
                     neighbors.push(Position::new(new_col as usize, new_row as usize));
@@ -816,9 +1380,9 @@ impl Game {
                 let new_row = row as isize + dy;
                 let new_col = col as isize + dx;
                 if new_row >= 0
-                    && new_row < BOARD_SIZE as isize
+                    && new_row < self.board_size as isize
                     && new_col >= 0
-                    && new_col < BOARD_SIZE as isize
+                    && new_col < self.board_size as isize
                     && self.board[new_row as usize][new_col as usize].is_some()
                 {
                     return true;
@@ -834,26 +1398,33 @@ impl Game {
         is_maximizing: bool,
         mut alpha: i32,
         mut beta: i32,
-        transposition_table: &mut HashMap<String, i32>,
+        limits: &SearchLimits,
+        transposition_table: &TranspositionTable,
     ) -> i32 {
-        let board_key = self.board_to_string();
-        if let Some(&cached_score) = transposition_table.get(&board_key) {
-            return cached_score;
+        let key = transposition::node_key(self.hash, is_maximizing, self.init_player);
+        let cached = transposition_table.get(key);
+        if let Some(score) = cached.and_then(|entry| entry.cutoff(depth, alpha, beta)) {
+            return score;
         }
 
-        let (score, mut threats) = self.evaluate();
+        let (score, mut threats) = self.evaluate(limits);
 
         if score >= MAX_SCORE || score <= -MAX_SCORE || depth == 0 {
             return score + if is_maximizing { -depth } else { depth };
         }
 
+        // The caller throws away an interrupted iteration, so any score will do here.
+        if limits.is_stopped() {
+            return 0;
+        }
+
         if self.is_full() {
             return 0;
         }
 
         if threats.is_empty() {
-            for row in 0..BOARD_SIZE {
-                for col in 0..BOARD_SIZE {
+            for row in 0..self.board_size {
+                for col in 0..self.board_size {
                     if self.board[row][col].is_some() {
                         continue;
                     }
@@ -867,60 +1438,79 @@ impl Game {
             }
         }
 
-        if is_maximizing {
-            let mut best_score = -MAX_SCORE;
-            for (_, Position { row, col }, _) in threats {
-                self.board[row][col] = Some(Player::O);
-                self.next_player = Player::X;
-
-                let score = self.minimax(depth - 1, false, alpha, beta, transposition_table);
-                self.next_player = Player::O;
-
-                self.board[row][col] = None;
-
-                best_score = best_score.max(score);
-                alpha = alpha.max(score);
-
-                if beta <= alpha {
-                    break;
-                }
+        let mover = if is_maximizing { Player::O } else { Player::X };
+        threats.retain(|(_, pos, _)| self.is_legal(pos, mover));
+        threats.truncate(limits.max_candidates);
+        // Try the best move from an earlier search first; it is the most likely to cut off.
+        if let Some(best) = cached.and_then(|entry| entry.best_move) {
+            if let Some(i) = threats.iter().position(|(_, pos, _)| *pos == best) {
+                threats[..=i].rotate_right(1);
             }
-            transposition_table.insert(board_key, best_score);
-            best_score
-        } else {
-            let mut best_score = MAX_SCORE;
-
-            for (_, Position { row, col }, _) in threats {
-                self.board[row][col] = Some(Player::X);
-                self.next_player = Player::O;
+        }
 
-                let score = self.minimax(depth - 1, true, alpha, beta, transposition_table);
+        let (alpha_orig, beta_orig) = (alpha, beta);
+        let mut best_score = if is_maximizing { -MAX_SCORE } else { MAX_SCORE };
+        let mut best_move = None;
+        for (_, pos, _) in threats {
+            self.place(&pos, mover);
+            self.next_player = mover.opponent();
+
+            let score = self.minimax(
+                depth - 1,
+                !is_maximizing,
+                alpha,
+                beta,
+                limits,
+                transposition_table,
+            );
 
-                self.board[row][col] = None;
-                self.next_player = Player::X;
+            self.next_player = mover;
+            self.remove(&pos);
 
-                best_score = best_score.min(score);
+            let improved = if is_maximizing {
+                score > best_score
+            } else {
+                score < best_score
+            };
+            if improved {
+                best_score = score;
+                best_move = Some(pos);
+            }
+            if is_maximizing {
+                alpha = alpha.max(score);
+            } else {
                 beta = beta.min(score);
-                if beta <= alpha {
-                    break;
-                }
             }
-            transposition_table.insert(board_key, best_score);
-            best_score
+            if beta <= alpha {
+                break;
+            }
+        }
+
+        if limits.is_stopped() {
+            return best_score;
         }
+        let bound = if best_score <= alpha_orig {
+            Bound::Upper
+        } else if best_score >= beta_orig {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        transposition_table.store(TtEntry {
+            key,
+            depth,
+            score: best_score,
+            bound,
+            best_move,
+        });
+        best_score
     }
 }
-fn moves_to_string(vect: &[Option<Player>]) -> String {
-    vect.iter()
-        .map(|mv| match mv {
-            Some(Player::O) => "o",
-            Some(Player::X) => "x",
-            None => "_",
-        })
-        .collect::<String>()
+fn empty_board(board_size: usize) -> Vec<Vec<Option<Player>>> {
+    vec![vec![None; board_size]; board_size]
 }
 
-#[derive(Debug, sqlx::Type, Serialize, Deserialize, Clone, Eq, PartialEq, Copy)]
+#[derive(Debug, sqlx::Type, Serialize, Deserialize, Clone, Eq, PartialEq, Copy, Hash)]
 #[sqlx(type_name = "player", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum Player {
@@ -928,6 +1518,24 @@ pub enum Player {
     O,
 }
 
+impl Player {
+    pub fn opponent(&self) -> Player {
+        match self {
+            Player::X => Player::O,
+            Player::O => Player::X,
+        }
+    }
+}
+
+impl Display for Player {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Player::X => write!(f, "X"),
+            Player::O => write!(f, "O"),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub struct Move {
     pub position: Position,
@@ -938,18 +1546,158 @@ impl Move {
     pub fn new(player: Player, position: Position) -> Self {
         Self { position, player }
     }
+
+    /// The move in notation on a `board_size` board: its player and position, e.g. `Xh8`.
+    pub fn to_notation(&self, board_size: usize) -> String {
+        format!("{}{}", self.player, self.position.to_notation(board_size))
+    }
+
+    /// Reads a move written by [`Move::to_notation`].
+    pub fn parse(text: &str, board_size: usize) -> Result<Self> {
+        let player = match text.chars().next() {
+            Some('X') => Player::X,
+            Some('O') => Player::O,
+            _ => return Err(anyhow::anyhow!("Invalid move {text:?}")),
+        };
+        Ok(Move::new(player, Position::parse(&text[1..], board_size)?))
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+/// A move on a `DEFAULT_BOARD_SIZE` board; other sizes use [`Move::to_notation`].
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_notation(DEFAULT_BOARD_SIZE))
+    }
+}
+
+/// A move on a `DEFAULT_BOARD_SIZE` board; other sizes use [`Move::parse`].
+impl FromStr for Move {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Move::parse(text, DEFAULT_BOARD_SIZE)
+    }
+}
+
+/// Parses moves on a `board_size` board separated by spaces or commas, e.g. `h8 i9 h9`. A move
+/// may name its player, e.g. `Oh8`; a bare position is played by the opponent of the move
+/// before it, or by `first` at the start.
+pub fn parse_moves(text: &str, first: Player, board_size: usize) -> Result<Vec<Move>> {
+    let mut moves: Vec<Move> = vec![];
+    for token in text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty())
+    {
+        let mv = match Position::parse(token, board_size) {
+            Ok(position) => {
+                let player = moves.last().map_or(first, |mv| mv.player.opponent());
+                Move::new(player, position)
+            }
+            Err(_) => Move::parse(token, board_size)?,
+        };
+        moves.push(mv);
+    }
+    Ok(moves)
+}
+
+/// A shape on the board: `stones` of `player`'s in a pattern that a stone at `position` would
+/// strengthen.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Threat {
+    pub stones: usize,
+    pub position: Position,
+    pub player: Player,
+}
+
+/// A move suggested by the engine and its score for the side to move.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    pub position: Position,
+    pub score: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Position {
     pub col: usize,
     pub row: usize,
 }
 
+/// Positions are written as a column letter from `a` on the left, going on with `aa`, `ab`...
+/// past `z`, and a row number from 1 at the bottom, so the centre of a 15x15 board is `h8`.
+/// Rows are stored from the top, so both directions need the board size.
 impl Position {
     pub fn new(col: usize, row: usize) -> Self {
         Self { row, col }
     }
+
+    /// The position in notation on a `board_size` board, e.g. `h8`.
+    pub fn to_notation(&self, board_size: usize) -> String {
+        let letter = |index: usize| char::from(b'a' + index as u8);
+        let mut text = String::new();
+        if self.col >= 26 {
+            text.push(letter(self.col / 26 - 1));
+        }
+        text.push(letter(self.col % 26));
+        format!("{text}{}", board_size.saturating_sub(self.row))
+    }
+
+    /// Reads a position written by [`Position::to_notation`]. Letters may be either case.
+    /// Fails for text that is not a position or is off the board.
+    pub fn parse(text: &str, board_size: usize) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("Invalid position {text:?}");
+        let split = text
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .ok_or_else(invalid)?;
+        let (letters, digits) = text.split_at(split);
+        let col = letters
+            .bytes()
+            .try_fold(0usize, |col, letter| {
+                let letter = letter.to_ascii_lowercase();
+                letter
+                    .is_ascii_lowercase()
+                    .then(|| col * 26 + usize::from(letter - b'a') + 1)
+            })
+            .filter(|col| (1..=board_size).contains(col))
+            .ok_or_else(invalid)?;
+        let row: usize = digits.parse().map_err(|_| invalid())?;
+        if !(1..=board_size).contains(&row) {
+            return Err(invalid());
+        }
+        Ok(Position::new(col - 1, board_size - row))
+    }
+}
+
+/// A position on a `DEFAULT_BOARD_SIZE` board; other sizes use [`Position::to_notation`].
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_notation(DEFAULT_BOARD_SIZE))
+    }
+}
+
+/// A position on a `DEFAULT_BOARD_SIZE` board; other sizes use [`Position::parse`].
+impl FromStr for Position {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Position::parse(text, DEFAULT_BOARD_SIZE)
+    }
+}
+
+/// A past game as one player's history lists it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GameSummary {
+    pub id: Uuid,
+    pub game_type: GameType,
+    /// The colour the player had; `None` for a game they imported rather than played.
+    pub colour: Option<Player>,
+    /// `Uuid::nil()` for the bot; `None` if the seat was never taken or the game was imported.
+    pub opponent: Option<Uuid>,
+    pub bot_level: Option<BotLevel>,
+    pub result: Option<GameResult>,
+    pub board_size: usize,
+    pub rule_set: RuleSet,
+    pub moves: usize,
+    pub created_at: Option<DateTime<Utc>>,
 }
 
 pub struct GameDb {
@@ -959,11 +1707,18 @@ pub struct GameDb {
     pub o: Option<Uuid>,
     pub moves: serde_json::Value,
     pub winner: serde_json::Value,
+    pub result: Option<GameResult>,
     pub init_player: Player,
     pub game_type: GameType,
+    pub bot_level: Option<BotLevel>,
+    pub seed: i64,
     pub x_status: PlayerStatus,
     pub o_status: PlayerStatus,
     pub status: GameStatus,
+    pub board_size: i16,
+    pub rule_set: RuleSet,
+    pub opening: Opening,
+    pub opening_step: OpeningStep,
 }
 
 #[derive(Deserialize)]
@@ -989,24 +1744,45 @@ impl TryFrom<GameDb> for Game {
             },
             None => game.init_player,
         };
-        let mut board = [[None; BOARD_SIZE]; BOARD_SIZE];
+        let board_size = game.board_size as usize;
+        let mut board = empty_board(board_size);
+        let mut hash = 0;
+        let mut bits = BitBoard::new(board_size);
         moves.iter().for_each(|mv| {
-            board[mv.position.row][mv.position.col] = Some(mv.player);
+            if let Some(cell) = board
+                .get_mut(mv.position.row)
+                .and_then(|row| row.get_mut(mv.position.col))
+            {
+                *cell = Some(mv.player);
+                hash ^= transposition::stone_key(&mv.position, mv.player);
+                bits.place(&mv.position, mv.player);
+            }
         });
         let winner: Option<Vec<Move>> = serde_json::from_value(game.winner)?;
         let game = Game {
             room_id: game.room_id,
             board,
+            board_size,
+            rule_set: game.rule_set,
+            init_player: game.init_player,
+            opening: game.opening,
+            opening_step: game.opening_step,
             id: game.id,
             moves,
             next_player,
             winner,
+            result: game.result,
             o: game.o,
             x: game.x,
             x_status: game.x_status,
             o_status: game.o_status,
             game_type: game.game_type,
+            bot_level: game.bot_level,
+            seed: game.seed,
+            invite_code: None,
             status: game.status,
+            hash,
+            bits,
         };
 
         Ok(game)
@@ -1029,15 +1805,50 @@ pub enum GameEvent {
         moves: Vec<Move>,
         last_move: Move,
     },
-    MiniMax {
-        position: Position,
-        score: i32,
+    Forbidden {
+        mv: Move,
+        reason: ForbiddenMove,
+    },
+    OpeningMove {
+        mv: Move,
+    },
+    Opening {
+        step: OpeningStep,
+    },
+    ChooseColour {
+        player: Player,
+    },
+    PlaceTwo,
+    Result {
+        result: GameResult,
+    },
+    Resign,
+    OfferDraw,
+    DrawOffered {
+        player: Player,
+    },
+    AcceptDraw,
+    /// Asks for the best moves for the sender, who must be the side to move.
+    Hint {
+        #[serde(default)]
+        count: Option<usize>,
+    },
+    /// The answer to `Hint`, shown only to `user_id`.
+    Hints {
+        user_id: Uuid,
+        candidates: Vec<Candidate>,
+        remaining: usize,
     },
     Message {
         msg: String,
         id: Uuid,
         user: Option<User>,
     },
+    /// Sent for a `/highlight h8` chat message: marks `position` on everyone's board.
+    Highlight {
+        position: Position,
+        user_id: Uuid,
+    },
     Status {
         status: GameStatus,
     },
@@ -0,0 +1,297 @@
+//! Evaluation profiles: the shapes the engine scores and what each is worth.
+//!
+//! A profile is read from TOML or JSON, chosen by file extension. Every field is optional and
+//! falls back to the built-in profile:
+//!
+//! ```toml
+//! name = "aggressive"
+//! attack = 1.2
+//! defence = 0.9
+//!
+//! [[patterns]]
+//! pattern = "_ooo_"
+//! score = 300
+//! count = 1
+//! ```
+//!
+//! Patterns are written for the owner as `o`, with `x` for the opponent and `_` for an empty
+//! cell; each is also matched with the colours swapped. A pattern found on a line scores for
+//! its owner, scaled by `attack` for the bot's own shapes and `defence` for its opponent's.
+
+use crate::bitboard::Pattern;
+use crate::models::{BotLevel, BotSettings, Player};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Longest pattern accepted; lines are at most `MAX_BOARD_SIZE` cells and patterns are
+/// matched against 32-bit masks.
+const MAX_PATTERN_LEN: usize = 16;
+
+/// The built-in shapes with their scores and counts: fours, then open and broken threes.
+const DEFAULT_PATTERNS: [(&str, i32, usize); 9] = [
+    ("_oooo_", 400, 1),
+    ("_oooox", 400, 1),
+    ("xoooo_", 400, 1),
+    ("oo_oo", 400, 1),
+    ("o_ooo", 400, 1),
+    ("ooo_o", 400, 1),
+    ("_ooo_", 300, 1),
+    ("_oo_o_", 300, 2),
+    ("_o_oo_", 300, 2),
+];
+/// Score tiers, counted from the top, whose shapes make a double threat when two lines
+/// through one move hold them: fours and threes in the built-in profile.
+const WINNING_TIERS: usize = 2;
+
+lazy_static::lazy_static! {
+    static ref DEFAULT_PROFILE: Arc<EvalProfile> = Arc::new(EvalProfile::default());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeightedPattern {
+    pub pattern: String,
+    pub score: i32,
+    /// How many threats one match counts as, both among the candidate moves and in the
+    /// score.
+    #[serde(default = "default_count")]
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProfileFile {
+    #[serde(default = "default_name")]
+    name: String,
+    #[serde(default = "default_patterns")]
+    patterns: Vec<WeightedPattern>,
+    #[serde(default = "default_weight")]
+    attack: f64,
+    #[serde(default = "default_weight")]
+    defence: f64,
+}
+
+fn default_name() -> String {
+    "default".to_string()
+}
+
+fn default_patterns() -> Vec<WeightedPattern> {
+    DEFAULT_PATTERNS
+        .iter()
+        .map(|&(pattern, score, count)| WeightedPattern {
+            pattern: pattern.to_string(),
+            score,
+            count,
+        })
+        .collect()
+}
+
+fn default_count() -> usize {
+    1
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "ProfileFile", into = "ProfileFile")]
+pub struct EvalProfile {
+    pub name: String,
+    pub patterns: Vec<WeightedPattern>,
+    /// Multiplier for the bot's own shapes.
+    pub attack: f64,
+    /// Multiplier for the opponent's shapes.
+    pub defence: f64,
+    /// `patterns` for O followed by the same patterns for X.
+    compiled: Arc<Vec<Pattern>>,
+    /// The patterns of the top `WINNING_TIERS` scores, for both colours.
+    winning: Arc<Vec<Pattern>>,
+}
+
+impl Default for EvalProfile {
+    fn default() -> Self {
+        Self::try_from(ProfileFile {
+            name: default_name(),
+            patterns: default_patterns(),
+            attack: default_weight(),
+            defence: default_weight(),
+        })
+        .expect("built-in patterns are valid")
+    }
+}
+
+impl TryFrom<ProfileFile> for EvalProfile {
+    type Error = anyhow::Error;
+
+    fn try_from(file: ProfileFile) -> Result<Self> {
+        for WeightedPattern { pattern, .. } in &file.patterns {
+            if pattern.is_empty()
+                || pattern.len() > MAX_PATTERN_LEN
+                || !pattern.chars().all(|c| matches!(c, 'o' | 'x' | '_'))
+            {
+                return Err(anyhow!("invalid pattern {pattern:?}"));
+            }
+        }
+        if !(file.attack > 0.0 && file.defence > 0.0) {
+            return Err(anyhow!("attack and defence must be positive"));
+        }
+        let swapped = |pattern: &str| -> String {
+            pattern
+                .chars()
+                .map(|c| match c {
+                    'o' => 'x',
+                    'x' => 'o',
+                    c => c,
+                })
+                .collect()
+        };
+        let compile = |patterns: &[&WeightedPattern]| -> Vec<Pattern> {
+            let own = patterns
+                .iter()
+                .map(|weighted| (weighted.pattern.clone(), weighted));
+            let theirs = patterns
+                .iter()
+                .map(|weighted| (swapped(&weighted.pattern), weighted));
+            own.chain(theirs)
+                .map(|(pattern, weighted)| {
+                    let mut pattern = Pattern::compile(&pattern, weighted.score);
+                    pattern.count = weighted.count;
+                    pattern
+                })
+                .collect()
+        };
+        let mut tiers: Vec<i32> = file
+            .patterns
+            .iter()
+            .map(|weighted| weighted.score)
+            .collect();
+        tiers.sort_unstable_by(|a, b| b.cmp(a));
+        tiers.dedup();
+        let lowest_winning = tiers
+            .get(WINNING_TIERS - 1)
+            .or(tiers.last())
+            .copied()
+            .unwrap_or_default();
+        let all: Vec<&WeightedPattern> = file.patterns.iter().collect();
+        let winning: Vec<&WeightedPattern> = file
+            .patterns
+            .iter()
+            .filter(|weighted| weighted.score >= lowest_winning)
+            .collect();
+        Ok(Self {
+            compiled: Arc::new(compile(&all)),
+            winning: Arc::new(compile(&winning)),
+            name: file.name,
+            patterns: file.patterns,
+            attack: file.attack,
+            defence: file.defence,
+        })
+    }
+}
+
+impl From<EvalProfile> for ProfileFile {
+    fn from(profile: EvalProfile) -> Self {
+        Self {
+            name: profile.name,
+            patterns: profile.patterns,
+            attack: profile.attack,
+            defence: profile.defence,
+        }
+    }
+}
+
+impl EvalProfile {
+    /// The built-in profile, shared so settings that use it are cheap to build.
+    pub fn shared_default() -> Arc<Self> {
+        DEFAULT_PROFILE.clone()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        parse_file(path.as_ref())
+    }
+
+    /// The compiled patterns, in the order they are reported on a line.
+    pub fn compiled(&self) -> &Arc<Vec<Pattern>> {
+        &self.compiled
+    }
+
+    /// The fours and threes the bot looks for when it tries to make or stop a double threat.
+    pub fn winning_threats(&self) -> &[Pattern] {
+        &self.winning
+    }
+
+    /// The score of a shape owned by `owner` when the bot plays `bot`.
+    pub fn weigh(&self, score: i32, owner: Player, bot: Player) -> f64 {
+        let weight = if owner == bot {
+            self.attack
+        } else {
+            self.defence
+        };
+        score as f64 * weight
+    }
+}
+
+/// A profile per bot level, for levels that should not use the default one.
+#[derive(Debug, Clone)]
+pub struct EvalProfiles {
+    pub default: Arc<EvalProfile>,
+    pub levels: HashMap<BotLevel, Arc<EvalProfile>>,
+}
+
+impl Default for EvalProfiles {
+    fn default() -> Self {
+        Self {
+            default: EvalProfile::shared_default(),
+            levels: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProfilesFile {
+    #[serde(default)]
+    default: Option<EvalProfile>,
+    #[serde(default)]
+    levels: HashMap<BotLevel, EvalProfile>,
+}
+
+impl EvalProfiles {
+    /// Reads `{ "default": { ... }, "levels": { "expert": { ... } } }`, or the same as TOML
+    /// tables. Both keys are optional.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file: ProfilesFile = parse_file(path.as_ref())?;
+        Ok(Self {
+            default: file
+                .default
+                .map(Arc::new)
+                .unwrap_or_else(EvalProfile::shared_default),
+            levels: file
+                .levels
+                .into_iter()
+                .map(|(level, profile)| (level, Arc::new(profile)))
+                .collect(),
+        })
+    }
+
+    pub fn for_level(&self, level: BotLevel) -> Arc<EvalProfile> {
+        self.levels.get(&level).unwrap_or(&self.default).clone()
+    }
+
+    /// The level's settings with its profile.
+    pub fn settings(&self, level: BotLevel) -> BotSettings {
+        BotSettings {
+            profile: self.for_level(level),
+            ..level.settings()
+        }
+    }
+}
+
+fn parse_file<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T> {
+    let text = std::fs::read_to_string(path)?;
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => Ok(toml::from_str(&text)?),
+        _ => Ok(serde_json::from_str(&text)?),
+    }
+}
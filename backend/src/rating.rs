@@ -0,0 +1,159 @@
+//! Glicko-2 ratings for online games.
+//!
+//! Every rated game is its own rating period, so ratings move after each game instead of in
+//! batches. Deviation only shrinks with play; it does not grow back while a player is away.
+//! See Glickman, "Example of the Glicko-2 system".
+
+use crate::models::{BotLevel, Game, GameResult, GameType, Player};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use uuid::Uuid;
+
+pub const DEFAULT_RATING: f64 = 1500.0;
+pub const DEFAULT_DEVIATION: f64 = 350.0;
+pub const DEFAULT_VOLATILITY: f64 = 0.06;
+/// Players whose deviation is above this have played too little for their rating to mean much.
+pub const PROVISIONAL_DEVIATION: f64 = 110.0;
+/// Deviation given to the bots' fixed ratings.
+const BOT_DEVIATION: f64 = 50.0;
+/// Constrains how fast volatility changes.
+const TAU: f64 = 0.5;
+/// Converts between the Glicko scale and the Glicko-2 scale.
+const SCALE: f64 = 173.7178;
+const CONVERGENCE: f64 = 0.000_001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            rating: DEFAULT_RATING,
+            deviation: DEFAULT_DEVIATION,
+            volatility: DEFAULT_VOLATILITY,
+        }
+    }
+}
+
+impl Rating {
+    /// The rating after one game against `opponent`, `score` being 1 for a win, 0.5 for a
+    /// draw and 0 for a loss.
+    pub fn update(&self, opponent: &Rating, score: f64) -> Rating {
+        let mu = (self.rating - DEFAULT_RATING) / SCALE;
+        let phi = self.deviation / SCALE;
+        let opponent_mu = (opponent.rating - DEFAULT_RATING) / SCALE;
+        let opponent_phi = opponent.deviation / SCALE;
+
+        let g = 1.0 / (1.0 + 3.0 * opponent_phi.powi(2) / PI.powi(2)).sqrt();
+        let expected = 1.0 / (1.0 + (-g * (mu - opponent_mu)).exp());
+        let variance = 1.0 / (g.powi(2) * expected * (1.0 - expected));
+        let delta = variance * g * (score - expected);
+
+        let volatility = self.new_volatility(phi, variance, delta);
+        let phi_star = (phi.powi(2) + volatility.powi(2)).sqrt();
+        let new_phi = 1.0 / (1.0 / phi_star.powi(2) + 1.0 / variance).sqrt();
+        let new_mu = mu + new_phi.powi(2) * g * (score - expected);
+        Rating {
+            rating: new_mu * SCALE + DEFAULT_RATING,
+            deviation: (new_phi * SCALE).min(DEFAULT_DEVIATION),
+            volatility,
+        }
+    }
+
+    /// Step 5 of the paper: the Illinois algorithm on the volatility equation.
+    fn new_volatility(&self, phi: f64, variance: f64, delta: f64) -> f64 {
+        let a = self.volatility.powi(2).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta.powi(2) - phi.powi(2) - variance - ex)
+                / (2.0 * (phi.powi(2) + variance + ex).powi(2))
+                - (x - a) / TAU.powi(2)
+        };
+        let mut lower = a;
+        let mut upper = if delta.powi(2) > phi.powi(2) + variance {
+            (delta.powi(2) - phi.powi(2) - variance).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * TAU
+        };
+        let mut f_lower = f(lower);
+        let mut f_upper = f(upper);
+        while (upper - lower).abs() > CONVERGENCE {
+            let c = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_c = f(c);
+            if f_c * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+            upper = c;
+            f_upper = f_c;
+        }
+        (lower / 2.0).exp()
+    }
+
+    pub fn is_provisional(&self) -> bool {
+        self.deviation > PROVISIONAL_DEVIATION
+    }
+}
+
+/// The fixed rating each bot level is shown with. Bot games are not rated.
+pub fn bot_rating(level: BotLevel) -> Rating {
+    let rating = match level {
+        BotLevel::Beginner => 800.0,
+        BotLevel::Casual => 1200.0,
+        BotLevel::Strong => 1700.0,
+        BotLevel::Expert => 2100.0,
+    };
+    Rating {
+        rating,
+        deviation: BOT_DEVIATION,
+        volatility: DEFAULT_VOLATILITY,
+    }
+}
+
+/// Whether `game` changes its players' ratings: a finished normal game between two people.
+pub fn is_rated(game: &Game) -> bool {
+    match (&game.game_type, game.result, game.x, game.o) {
+        (GameType::Normal, Some(_), Some(x), Some(o)) => x != o && !x.is_nil() && !o.is_nil(),
+        _ => false,
+    }
+}
+
+/// `player`'s score in a game that ended with `result`.
+pub fn score(result: GameResult, player: Player) -> f64 {
+    match result.winner() {
+        Some(winner) if winner == player => 1.0,
+        Some(_) => 0.0,
+        None => 0.5,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerRating {
+    pub user_id: Uuid,
+    #[serde(flatten)]
+    pub rating: Rating,
+    pub games: i32,
+    pub provisional: bool,
+}
+
+/// A player's rating after one game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatingChange {
+    pub game_id: Uuid,
+    #[serde(flatten)]
+    pub rating: Rating,
+    /// Rating points won or lost in the game.
+    pub change: f64,
+    pub created_at: DateTime<Utc>,
+}
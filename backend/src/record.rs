@@ -0,0 +1,364 @@
+//! Game records in the formats desktop tools read: Gomocup PSQ, RenjuNet RIF and SGF (`GM[4]`).
+//!
+//! Records list the moves from an empty board, the first stone being black. Imports replay
+//! every move through `Game::play`, so a record with an illegal move, or moves after the five,
+//! is rejected as a whole.
+
+use crate::models::{
+    Game, GameResult, GameStatus, GameType, Move, Player, Position, RuleSet, MAX_BOARD_SIZE,
+    MIN_BOARD_SIZE,
+};
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// RenjuNet games are played on 15x15 and RIF files do not say otherwise.
+const RIF_BOARD_SIZE: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    Psq,
+    Rif,
+    Sgf,
+}
+
+impl FromStr for RecordFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        match text.to_ascii_lowercase().as_str() {
+            "psq" => Ok(RecordFormat::Psq),
+            "rif" => Ok(RecordFormat::Rif),
+            "sgf" => Ok(RecordFormat::Sgf),
+            _ => Err(anyhow!("unknown record format {text}")),
+        }
+    }
+}
+
+impl RecordFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordFormat::Psq => "psq",
+            RecordFormat::Rif => "rif",
+            RecordFormat::Sgf => "sgf",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            RecordFormat::Psq => "text/plain; charset=utf-8",
+            RecordFormat::Rif => "application/xml; charset=utf-8",
+            RecordFormat::Sgf => "application/x-go-sgf; charset=utf-8",
+        }
+    }
+
+    /// Whether games on a `board_size` board can be written in the format; RIF only holds
+    /// 15x15 games.
+    pub fn supports(&self, board_size: usize) -> bool {
+        *self != RecordFormat::Rif || board_size == RIF_BOARD_SIZE
+    }
+}
+
+/// Settings a record may not carry itself.
+#[derive(Debug, Clone, Copy)]
+pub struct ImportOptions {
+    pub rule_set: RuleSet,
+    /// Board size for RIF, which has none of its own.
+    pub board_size: Option<usize>,
+}
+
+pub fn export(game: &Game, format: RecordFormat) -> String {
+    match format {
+        RecordFormat::Psq => export_psq(game),
+        RecordFormat::Rif => export_rif(game),
+        RecordFormat::Sgf => export_sgf(game),
+    }
+}
+
+/// Every game in `text`, replayed and checked. PSQ holds one game, RIF and SGF files may hold
+/// several. Seats are left to the caller.
+pub fn import(text: &str, format: RecordFormat, options: ImportOptions) -> Result<Vec<Game>> {
+    let games = match format {
+        RecordFormat::Psq => vec![parse_psq(text)?],
+        RecordFormat::Rif => parse_rif(text, options.board_size.unwrap_or(RIF_BOARD_SIZE))?,
+        RecordFormat::Sgf => parse_sgf(text)?,
+    };
+    if games.is_empty() {
+        return Err(anyhow!("no games found"));
+    }
+    games
+        .into_iter()
+        .map(|(board_size, moves)| replay(board_size, options.rule_set, &moves))
+        .collect()
+}
+
+/// Plays `moves` from an empty board, black (`X`) first, and ends the game on a five or a full
+/// board. A move by the player not on turn rejects the record.
+fn replay(board_size: usize, rule_set: RuleSet, moves: &[Move]) -> Result<Game> {
+    if !(MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&board_size) {
+        return Err(anyhow!("unsupported board size {board_size}"));
+    }
+    let mut game = Game::new(Uuid::new_v4(), Player::X, GameType::Private, board_size);
+    game.rule_set = rule_set;
+    game.x = Some(Uuid::nil());
+    game.o = Some(Uuid::nil());
+    for (index, mv) in moves.iter().enumerate() {
+        let position = mv.position;
+        let notation = position.to_notation(board_size);
+        if mv.player != game.next_player {
+            return Err(anyhow!("move {} ({notation}): out of turn", index + 1));
+        }
+        game.play(mv)
+            .map_err(|error| anyhow!("move {} ({notation}): {error}", index + 1))?;
+        if let Some(win) = game.check_winning_move(&position)? {
+            game.winner = Some(win);
+            game.finish(GameResult::win(mv.player));
+        } else if game.is_full() {
+            game.finish(GameResult::DrawFullBoard);
+        }
+    }
+    game.status = GameStatus::Ended;
+    Ok(game)
+}
+
+/// Black's score: 1 for a win, 0.5 for a draw, 0 for a loss.
+fn black_score(game: &Game) -> Option<f64> {
+    let result = game.result?;
+    Some(match result.winner() {
+        Some(winner) if winner == game.init_player => 1.0,
+        Some(_) => 0.0,
+        None => 0.5,
+    })
+}
+
+/// `Piskvorky 15x15, 11:11, 0`, then `x,y,time` per move counted from 1 at the top left.
+fn export_psq(game: &Game) -> String {
+    let mut lines = vec![format!(
+        "Piskvorky {size}x{size}, 11:11, 0",
+        size = game.board_size
+    )];
+    for mv in &game.moves {
+        lines.push(format!("{},{},0", mv.position.col + 1, mv.position.row + 1));
+    }
+    lines.join("\n") + "\n"
+}
+
+fn parse_psq(text: &str) -> Result<(usize, Vec<Move>)> {
+    let mut lines = text.lines().map(str::trim);
+    let header = lines.next().ok_or_else(|| anyhow!("empty PSQ record"))?;
+    let board_size = header
+        .split([' ', ','])
+        .find_map(|word| {
+            let (width, height) = word.split_once('x')?;
+            let width: usize = width.parse().ok()?;
+            (height.parse::<usize>().ok()? == width).then_some(width)
+        })
+        .ok_or_else(|| anyhow!("PSQ header without a square board size"))?;
+    // Moves end at the first line that is not `x,y,time`; engine names and the like follow.
+    let moves = lines
+        .map_while(|line| {
+            let mut fields = line.split(',').map(|field| field.trim().parse::<usize>());
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(Ok(col)), Some(Ok(row)), Some(Ok(_))) if col > 0 && row > 0 => {
+                    Some(Position::new(col - 1, row - 1))
+                }
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    Ok((board_size, alternating(&moves)))
+}
+
+/// PSQ and RIF list positions only: black plays the first and colours alternate.
+fn alternating(positions: &[Position]) -> Vec<Move> {
+    let mut player = Player::X;
+    positions
+        .iter()
+        .map(|&position| {
+            let mv = Move::new(player, position);
+            player = player.opponent();
+            mv
+        })
+        .collect()
+}
+
+/// A RenLib-style database with the game's moves in renju notation.
+fn export_rif(game: &Game) -> String {
+    let moves: Vec<String> = game
+        .moves
+        .iter()
+        .map(|mv| mv.position.to_notation(game.board_size))
+        .collect();
+    let result = black_score(game)
+        .map(|score| format!(" bresult=\"{score}\""))
+        .unwrap_or_default();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <database>\n\
+         <games>\n\
+         <game id=\"1\"{result}>\n\
+         <move>{}</move>\n\
+         </game>\n\
+         </games>\n\
+         </database>\n",
+        moves.join(" ")
+    )
+}
+
+fn parse_rif(text: &str, board_size: usize) -> Result<Vec<(usize, Vec<Move>)>> {
+    let mut games = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find("<move>") {
+        rest = &rest[start + "<move>".len()..];
+        let end = rest
+            .find("</move>")
+            .ok_or_else(|| anyhow!("unclosed <move> element"))?;
+        let moves = rest[..end]
+            .split_whitespace()
+            .map(|token| Position::parse(token, board_size))
+            .collect::<Result<Vec<_>>>()?;
+        games.push((board_size, alternating(&moves)));
+        rest = &rest[end..];
+    }
+    Ok(games)
+}
+
+/// `(;GM[4]FF[4]SZ[15]RE[B+];B[hh];W[ii]...)`, coordinates being column then row letters from
+/// `a` at the top left.
+fn export_sgf(game: &Game) -> String {
+    let black = game.init_player;
+    let mut sgf = format!("(;GM[4]FF[4]CA[UTF-8]SZ[{}]", game.board_size);
+    if let Some(result) = game.result {
+        let result = match result.winner() {
+            Some(winner) if winner == black => "B+",
+            Some(_) => "W+",
+            None => "0",
+        };
+        sgf.push_str(&format!("RE[{result}]"));
+    }
+    for mv in &game.moves {
+        let colour = if mv.player == black { 'B' } else { 'W' };
+        sgf.push_str(&format!(
+            ";{colour}[{}{}]",
+            sgf_coordinate(mv.position.col),
+            sgf_coordinate(mv.position.row)
+        ));
+    }
+    sgf.push_str(")\n");
+    sgf
+}
+
+fn sgf_coordinate(index: usize) -> char {
+    if index < 26 {
+        char::from(b'a' + index as u8)
+    } else {
+        char::from(b'A' + (index - 26) as u8)
+    }
+}
+
+fn parse_sgf_coordinate(letter: char) -> Option<usize> {
+    match letter {
+        'a'..='z' => Some(letter as usize - 'a' as usize),
+        'A'..='Z' => Some(letter as usize - 'A' as usize + 26),
+        _ => None,
+    }
+}
+
+/// The main line of every game tree in an SGF collection. Variations after the first are
+/// skipped.
+fn parse_sgf(text: &str) -> Result<Vec<(usize, Vec<Move>)>> {
+    let mut games = vec![];
+    let mut chars = text.chars().peekable();
+    let mut depth = 0;
+    // Per depth, whether a variation there has been read already.
+    let mut variation_read = vec![false];
+    let mut skip_until: Option<usize> = None;
+    let mut property = String::new();
+    // A name after a value starts a new property; values may repeat, as in `AB[aa][bb]`.
+    let mut after_value = false;
+    let mut board_size = 19;
+    let mut moves = vec![];
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => {
+                if skip_until.is_none() && depth > 0 && variation_read[depth] {
+                    skip_until = Some(depth);
+                }
+                depth += 1;
+                if variation_read.len() <= depth {
+                    variation_read.push(false);
+                }
+                variation_read[depth] = false;
+            }
+            ')' => {
+                if depth == 0 {
+                    return Err(anyhow!("unbalanced SGF parentheses"));
+                }
+                depth -= 1;
+                variation_read[depth] = true;
+                if skip_until == Some(depth) {
+                    skip_until = None;
+                } else if depth == 0 && skip_until.is_none() {
+                    games.push((board_size, std::mem::take(&mut moves)));
+                    board_size = 19;
+                }
+            }
+            '[' => {
+                let mut value = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => value.extend(chars.next()),
+                        ']' => break,
+                        c => value.push(c),
+                    }
+                }
+                after_value = true;
+                if skip_until.is_some() {
+                    continue;
+                }
+                match property.as_str() {
+                    "GM" if value.trim() != "4" => {
+                        return Err(anyhow!("not a gomoku record (GM[{value}])"))
+                    }
+                    "SZ" => {
+                        board_size = value
+                            .trim()
+                            .parse()
+                            .map_err(|_| anyhow!("invalid SZ[{value}]"))?
+                    }
+                    "B" | "W" => {
+                        let player = if property == "B" {
+                            Player::X
+                        } else {
+                            Player::O
+                        };
+                        let mut letters = value.chars().map(parse_sgf_coordinate);
+                        match (letters.next(), letters.next(), letters.next()) {
+                            (Some(Some(col)), Some(Some(row)), None) => {
+                                moves.push(Move::new(player, Position::new(col, row)))
+                            }
+                            _ => return Err(anyhow!("invalid move {property}[{value}]")),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            ';' => {
+                property.clear();
+                after_value = false;
+            }
+            c if c.is_ascii_uppercase() => {
+                if after_value {
+                    property.clear();
+                    after_value = false;
+                }
+                property.push(c);
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err(anyhow!("unbalanced SGF parentheses"));
+    }
+    Ok(games)
+}
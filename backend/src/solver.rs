@@ -0,0 +1,255 @@
+//! Threat-space search: victory by continuous fours (VCF) and by continuous threats (VCT).
+//!
+//! Both searches answer "can `attacker`, moving now, force a win?" and return the main line
+//! of the win: the attacker's moves alternating with the defender's forced replies, ending
+//! with the attacker's five. They err on the side of caution: a line is only reported when
+//! every defence considered loses, and any counter-four by the defender counts as a defence.
+
+use crate::models::{BotSettings, Game, Move, Player, Position, SearchLimits};
+
+/// Searched positions after which a solver gives up and reports no win.
+const MAX_NODES: usize = 50_000;
+/// How far along a line from a three the defender's blocking stones are looked for.
+const DEFENCE_REACH: usize = 5;
+
+/// Searches for a win made only of fours, with at most `max_depth` attacker moves before the
+/// five.
+pub fn find_vcf(game: &Game, attacker: Player, max_depth: usize) -> Option<Vec<Move>> {
+    Solver::new(game, attacker, None).vcf(max_depth)
+}
+
+/// Searches for a win made of fours and open threes, with at most `max_depth` attacker
+/// moves before the five.
+pub fn find_vct(game: &Game, attacker: Player, max_depth: usize) -> Option<Vec<Move>> {
+    Solver::new(game, attacker, None).vct(max_depth)
+}
+
+/// The bot's move from threat-space search, if it settles the position: start a forced win
+/// of its own, or stop one of the opponent's. Depths come from `settings`. The searches give
+/// up, finding nothing, once `limits` runs out of time or is cancelled.
+pub fn threat_move(
+    game: &Game,
+    bot: Player,
+    settings: &BotSettings,
+    limits: &SearchLimits,
+) -> Option<Position> {
+    let opponent = bot.opponent();
+    let search = |game: &Game, attacker: Player, depth: usize, threes: bool| {
+        Solver::new(game, attacker, Some(limits)).search(depth, threes)
+    };
+    for (depth, threes) in [(settings.vcf_depth, false), (settings.vct_depth, true)] {
+        if depth == 0 {
+            continue;
+        }
+        if let Some(win) = search(game, bot, depth, threes) {
+            return win.first().map(|mv| mv.position);
+        }
+        if let Some(loss) = search(game, opponent, depth, threes) {
+            return Some(defend(game, bot, &loss, |game| {
+                search(game, opponent, depth, threes).is_none()
+            }));
+        }
+    }
+    None
+}
+
+/// A cell of the opponent's winning line after which `refuted` holds, or the opponent's first
+/// move if none of them does.
+fn defend(game: &Game, bot: Player, loss: &[Move], refuted: impl Fn(&Game) -> bool) -> Position {
+    let mut game = game.clone();
+    for mv in loss {
+        let pos = mv.position;
+        if game.board[pos.row][pos.col].is_some() || !game.is_legal(&pos, bot) {
+            continue;
+        }
+        game.place(&pos, bot);
+        let refuted = refuted(&game);
+        game.remove(&pos);
+        if refuted {
+            return pos;
+        }
+    }
+    loss[0].position
+}
+
+struct Solver<'a> {
+    game: Game,
+    attacker: Player,
+    defender: Player,
+    nodes: usize,
+    /// The bot search's deadline and cancel flag, when solving for the bot.
+    limits: Option<&'a SearchLimits<'a>>,
+}
+
+impl<'a> Solver<'a> {
+    fn new(game: &Game, attacker: Player, limits: Option<&'a SearchLimits<'a>>) -> Self {
+        Self {
+            game: game.clone(),
+            attacker,
+            defender: attacker.opponent(),
+            nodes: 0,
+            limits,
+        }
+    }
+
+    fn vcf(&mut self, depth: usize) -> Option<Vec<Move>> {
+        self.search(depth, false)
+    }
+
+    fn vct(&mut self, depth: usize) -> Option<Vec<Move>> {
+        self.search(depth, true)
+    }
+
+    fn search(&mut self, depth: usize, threes: bool) -> Option<Vec<Move>> {
+        self.nodes += 1;
+        if self.nodes > MAX_NODES || self.limits.is_some_and(SearchLimits::is_stopped) {
+            return None;
+        }
+        if let Some(&five) = self.fives(self.attacker).first() {
+            return Some(vec![Move::new(self.attacker, five)]);
+        }
+        if depth == 0 || !self.fives(self.defender).is_empty() {
+            return None;
+        }
+
+        for (four, gains) in self.four_moves(self.attacker) {
+            self.game.place(&four, self.attacker);
+            let line = match gains[..] {
+                [block, five, ..] => Some(vec![
+                    Move::new(self.defender, block),
+                    Move::new(self.attacker, five),
+                ]),
+                [block] if self.game.is_legal(&block, self.defender) => {
+                    self.game.place(&block, self.defender);
+                    let line = self.search(depth - 1, threes);
+                    self.game.remove(&block);
+                    line.map(|line| prepend(Move::new(self.defender, block), line))
+                }
+                _ => None,
+            };
+            self.game.remove(&four);
+            if let Some(line) = line {
+                return Some(prepend(Move::new(self.attacker, four), line));
+            }
+        }
+        if !threes {
+            return None;
+        }
+
+        for three in self.game.bits.window_cells(self.attacker, 2) {
+            if !self.game.is_legal(&three, self.attacker) {
+                continue;
+            }
+            self.game.place(&three, self.attacker);
+            let line = self.refute_defences(&three, depth);
+            self.game.remove(&three);
+            if let Some(line) = line {
+                return Some(prepend(Move::new(self.attacker, three), line));
+            }
+        }
+        None
+    }
+
+    /// With the attacker's stone just placed at `three`, the main line of a win against every
+    /// defence, if the stone made an open three and no defence holds.
+    fn refute_defences(&mut self, three: &Position, depth: usize) -> Option<Vec<Move>> {
+        let open_fours = self.open_four_points();
+        if open_fours.is_empty() || !self.four_moves(self.defender).is_empty() {
+            return None;
+        }
+        // Only stones on the lines of the three can stop it. Any other reply lets the
+        // attacker make an open four.
+        let mut defences: Vec<Position> = self
+            .cells_around(three)
+            .into_iter()
+            .filter(|pos| {
+                if !self.game.is_legal(pos, self.defender) {
+                    return false;
+                }
+                self.game.place(pos, self.defender);
+                let holds = self.open_four_points().is_empty();
+                self.game.remove(pos);
+                holds
+            })
+            .collect();
+        if defences.is_empty() {
+            // A double three: block one open four and show the win through another.
+            defences.push(open_fours[0]);
+        }
+
+        let mut main_line = None;
+        for defence in defences {
+            self.game.place(&defence, self.defender);
+            let line = self.search(depth - 1, true);
+            self.game.remove(&defence);
+            let line = line?;
+            main_line.get_or_insert_with(|| prepend(Move::new(self.defender, defence), line));
+        }
+        main_line
+    }
+
+    /// Empty cells where `player` would make a five.
+    fn fives(&mut self, player: Player) -> Vec<Position> {
+        let candidates = self.game.bits.window_cells(player, 4);
+        candidates
+            .into_iter()
+            .filter(|pos| {
+                if !self.game.is_legal(pos, player) {
+                    return false;
+                }
+                self.game.place(pos, player);
+                let five = matches!(self.game.check_winning_move(pos), Ok(Some(_)));
+                self.game.remove(pos);
+                five
+            })
+            .collect()
+    }
+
+    /// Moves that make a four for `player`, each with the cells where it would become five.
+    fn four_moves(&mut self, player: Player) -> Vec<(Position, Vec<Position>)> {
+        let candidates = self.game.bits.window_cells(player, 3);
+        candidates
+            .into_iter()
+            .filter_map(|pos| {
+                if !self.game.is_legal(&pos, player) {
+                    return None;
+                }
+                self.game.place(&pos, player);
+                let gains = self.fives(player);
+                self.game.remove(&pos);
+                (!gains.is_empty()).then_some((pos, gains))
+            })
+            .collect()
+    }
+
+    /// Cells where the attacker would make an open four or a double four.
+    fn open_four_points(&mut self) -> Vec<Position> {
+        self.four_moves(self.attacker)
+            .into_iter()
+            .filter(|(_, gains)| gains.len() >= 2)
+            .map(|(pos, _)| pos)
+            .collect()
+    }
+
+    /// Empty cells within `DEFENCE_REACH` of `pos` along its row, column and diagonals.
+    fn cells_around(&self, pos: &Position) -> Vec<Position> {
+        let bits = &self.game.bits;
+        let mut cells = vec![];
+        for (line, bit) in bits.lines_through(pos) {
+            let from = bit.saturating_sub(DEFENCE_REACH);
+            let to = (bit + DEFENCE_REACH).min(bits.line_len(line) - 1);
+            for other in from..=to {
+                let cell = bits.position(line, other);
+                if other != bit && self.game.board[cell.row][cell.col].is_none() {
+                    cells.push(cell);
+                }
+            }
+        }
+        cells
+    }
+}
+
+fn prepend(mv: Move, mut line: Vec<Move>) -> Vec<Move> {
+    line.insert(0, mv);
+    line
+}
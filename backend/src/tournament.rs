@@ -0,0 +1,363 @@
+//! Self-play between two bot configurations, for telling whether an engine change helps.
+//!
+//! Games start from random openings, each played twice with the colours swapped so neither
+//! configuration profits from a lopsided opening. The report gives the result from the point
+//! of view of the first configuration.
+
+use crate::models::{
+    BotLevel, BotSettings, Game, GameResult, GameType, Move, Player, Position, RuleSet,
+};
+use crate::profile::EvalProfile;
+use crate::transposition::TranspositionTable;
+use anyhow::{anyhow, Result};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// How far from the centre random opening stones are placed.
+const OPENING_RADIUS: usize = 2;
+/// Tries at drawing an opening not used yet before giving up on uniqueness.
+const OPENING_ATTEMPTS: usize = 100;
+/// Two-sided 95% quantile of the normal distribution.
+const Z_95: f64 = 1.96;
+
+/// A bot level with optional overrides, written `level[:key=value,...]`, for example
+/// `strong:depth=6,time=200`. Keys: `depth`, `time` (ms), `candidates`, `blunder`, `vcf`,
+/// `vct`, `threads` and `profile`, the path of an evaluation profile.
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    pub name: String,
+    pub settings: BotSettings,
+}
+
+impl FromStr for EngineConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let (level, overrides) = text.split_once(':').unwrap_or((text, ""));
+        let level: BotLevel = serde_json::from_value(serde_json::Value::String(level.into()))
+            .map_err(|_| anyhow!("unknown bot level {level}"))?;
+        let mut settings = level.settings();
+        for item in overrides.split(',').filter(|item| !item.is_empty()) {
+            let (key, value) = item
+                .split_once('=')
+                .ok_or_else(|| anyhow!("expected key=value, got {item}"))?;
+            match key {
+                "depth" => settings.depth = value.parse()?,
+                "time" => settings.time_budget = Duration::from_millis(value.parse()?),
+                "candidates" => settings.max_candidates = value.parse()?,
+                "blunder" => settings.blunder_rate = value.parse()?,
+                "vcf" => settings.vcf_depth = value.parse()?,
+                "vct" => settings.vct_depth = value.parse()?,
+                "threads" => settings.threads = value.parse()?,
+                "profile" => settings.profile = Arc::new(EvalProfile::load(value)?),
+                _ => return Err(anyhow!("unknown setting {key}")),
+            }
+        }
+        Ok(Self {
+            name: text.to_string(),
+            settings,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TournamentOptions {
+    /// Games to play. Rounded up to an even number so every opening is played from both
+    /// sides.
+    pub games: usize,
+    pub board_size: usize,
+    pub rule_set: RuleSet,
+    /// Random stones placed before the engines take over.
+    pub opening_plies: usize,
+    /// Games played at the same time.
+    pub jobs: usize,
+}
+
+/// A finished self-play game.
+#[derive(Debug, Clone, Serialize)]
+pub struct GameRecord {
+    /// Index of the random opening; both games of a pair share it.
+    pub opening: usize,
+    /// The colour the first configuration played.
+    pub a_plays: Player,
+    pub moves: Vec<Move>,
+    pub result: GameResult,
+    #[serde(skip)]
+    a_time: Duration,
+    #[serde(skip)]
+    a_moves: usize,
+    #[serde(skip)]
+    b_time: Duration,
+    #[serde(skip)]
+    b_moves: usize,
+}
+
+impl GameRecord {
+    /// 1 for a win of the first configuration, 0.5 for a draw and 0 for a loss.
+    pub fn score(&self) -> f64 {
+        match self.result.winner() {
+            Some(winner) if winner == self.a_plays => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    pub a_time: Duration,
+    pub a_moves: usize,
+    pub b_time: Duration,
+    pub b_moves: usize,
+    pub games: Vec<GameRecord>,
+}
+
+impl Report {
+    pub fn add(&mut self, record: GameRecord) {
+        match record.result.winner() {
+            Some(winner) if winner == record.a_plays => self.wins += 1,
+            Some(_) => self.losses += 1,
+            None => self.draws += 1,
+        }
+        self.a_time += record.a_time;
+        self.a_moves += record.a_moves;
+        self.b_time += record.b_time;
+        self.b_moves += record.b_moves;
+        self.games.push(record);
+    }
+
+    pub fn played(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The first configuration's average score per game.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.played().max(1) as f64
+    }
+
+    /// The Elo difference of the first configuration over the second, with the bounds of
+    /// its 95% confidence interval. Infinite when one side won every game.
+    pub fn elo(&self) -> (f64, f64, f64) {
+        let n = self.played().max(1) as f64;
+        let score = self.score();
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / n;
+        let margin = Z_95 * (variance / n).sqrt();
+        (
+            elo_difference(score),
+            elo_difference(score - margin),
+            elo_difference(score + margin),
+        )
+    }
+
+    pub fn a_average(&self) -> Duration {
+        average(self.a_time, self.a_moves)
+    }
+
+    pub fn b_average(&self) -> Duration {
+        average(self.b_time, self.b_moves)
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (elo, low, high) = self.elo();
+        writeln!(
+            f,
+            "Games: {}  W/D/L: {}/{}/{}  score: {:.1}%",
+            self.played(),
+            self.wins,
+            self.draws,
+            self.losses,
+            self.score() * 100.0
+        )?;
+        writeln!(
+            f,
+            "Elo difference: {elo:+.1} (95% CI {low:+.1} to {high:+.1})"
+        )?;
+        write!(
+            f,
+            "Average move time: A {:.1} ms, B {:.1} ms",
+            self.a_average().as_secs_f64() * 1000.0,
+            self.b_average().as_secs_f64() * 1000.0
+        )
+    }
+}
+
+fn elo_difference(score: f64) -> f64 {
+    if score <= 0.0 {
+        f64::NEG_INFINITY
+    } else if score >= 1.0 {
+        f64::INFINITY
+    } else {
+        -400.0 * (1.0 / score - 1.0).log10()
+    }
+}
+
+fn average(total: Duration, moves: usize) -> Duration {
+    if moves == 0 {
+        Duration::ZERO
+    } else {
+        total / moves as u32
+    }
+}
+
+/// Plays `options.games` games between `a` and `b` and collects the results.
+pub fn run(
+    a: &EngineConfig,
+    b: &EngineConfig,
+    options: &TournamentOptions,
+    rng: &mut impl Rng,
+) -> Report {
+    let openings = random_openings(
+        options.games.div_ceil(2),
+        options.opening_plies,
+        options.board_size,
+        options.rule_set,
+        rng,
+    );
+    let next = AtomicUsize::new(0);
+    let records = Mutex::new(vec![]);
+    std::thread::scope(|scope| {
+        for _ in 0..options.jobs.max(1) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= openings.len() * 2 {
+                    break;
+                }
+                let opening = index / 2;
+                let a_plays = if index.is_multiple_of(2) {
+                    Player::X
+                } else {
+                    Player::O
+                };
+                let mut record = play_game(a, b, a_plays, &openings[opening], options);
+                record.opening = opening;
+                records.lock().unwrap().push((index, record));
+            });
+        }
+    });
+
+    let mut records = records.into_inner().unwrap();
+    records.sort_by_key(|(index, _)| *index);
+    let mut report = Report::default();
+    for (_, record) in records {
+        report.add(record);
+    }
+    report
+}
+
+/// `count` distinct openings of `plies` legal stones near the centre, as far as that many
+/// exist.
+pub fn random_openings(
+    count: usize,
+    plies: usize,
+    board_size: usize,
+    rule_set: RuleSet,
+    rng: &mut impl Rng,
+) -> Vec<Vec<Position>> {
+    let centre = board_size / 2;
+    let cells: Vec<Position> = (centre.saturating_sub(OPENING_RADIUS)..=centre + OPENING_RADIUS)
+        .flat_map(|row| {
+            (centre.saturating_sub(OPENING_RADIUS)..=centre + OPENING_RADIUS)
+                .map(move |col| Position::new(col, row))
+        })
+        .filter(|pos| pos.col < board_size && pos.row < board_size)
+        .collect();
+    let mut seen = HashSet::new();
+    let mut openings = vec![];
+    while openings.len() < count {
+        let mut opening = vec![];
+        for _ in 0..OPENING_ATTEMPTS {
+            let mut game = new_game(board_size, rule_set);
+            opening = cells
+                .choose_multiple(rng, plies.min(cells.len()))
+                .copied()
+                .filter(|pos| game.play(&Move::new(game.next_player, *pos)).is_ok())
+                .collect();
+            if seen.insert(opening.clone()) {
+                break;
+            }
+        }
+        openings.push(opening);
+    }
+    openings
+}
+
+/// Plays one game from `opening`, `a` playing `a_plays`, until someone makes five or the
+/// board fills up.
+pub fn play_game(
+    a: &EngineConfig,
+    b: &EngineConfig,
+    a_plays: Player,
+    opening: &[Position],
+    options: &TournamentOptions,
+) -> GameRecord {
+    let mut game = new_game(options.board_size, options.rule_set);
+    for pos in opening {
+        game.play(&Move::new(game.next_player, *pos))
+            .expect("openings are legal");
+    }
+    let mut record = GameRecord {
+        opening: 0,
+        a_plays,
+        moves: vec![],
+        result: GameResult::DrawFullBoard,
+        a_time: Duration::ZERO,
+        a_moves: 0,
+        b_time: Duration::ZERO,
+        b_moves: 0,
+    };
+    let (a_table, b_table) = (TranspositionTable::default(), TranspositionTable::default());
+    let cancelled = AtomicBool::new(false);
+    while !game.is_full() {
+        let mover = game.next_player;
+        let (config, table) = if mover == a_plays {
+            (a, &a_table)
+        } else {
+            (b, &b_table)
+        };
+        let start = Instant::now();
+        let pos = game.find_bot_move(&config.settings, &cancelled, table);
+        let elapsed = start.elapsed();
+        if mover == a_plays {
+            record.a_time += elapsed;
+            record.a_moves += 1;
+        } else {
+            record.b_time += elapsed;
+            record.b_moves += 1;
+        }
+        // An engine without a legal move forfeits.
+        let Some(pos) = pos.filter(|pos| game.play(&Move::new(mover, *pos)).is_ok()) else {
+            record.result = GameResult::resign(mover);
+            break;
+        };
+        if matches!(game.check_winning_move(&pos), Ok(Some(_))) {
+            record.result = GameResult::win(mover);
+            break;
+        }
+    }
+    record.moves = game.moves;
+    record
+}
+
+fn new_game(board_size: usize, rule_set: RuleSet) -> Game {
+    let mut game = Game::new(Uuid::nil(), Player::X, GameType::Bot, board_size);
+    game.rule_set = rule_set;
+    game.x = Some(Uuid::nil());
+    game.o = Some(Uuid::nil());
+    game
+}
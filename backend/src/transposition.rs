@@ -0,0 +1,182 @@
+use crate::models::{Player, Position, MAX_BOARD_SIZE};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+/// Number of slots once the table is first written to. Must be a power of two.
+const TABLE_SIZE: usize = 1 << 16;
+
+lazy_static::lazy_static! {
+    static ref ZOBRIST: Zobrist = Zobrist::new();
+}
+
+struct Zobrist {
+    stones: Vec<[u64; 2]>,
+    maximizing: u64,
+    o_is_black: u64,
+}
+
+impl Zobrist {
+    fn new() -> Self {
+        // Fixed seed so hashes are stable between runs, which keeps tests reproducible.
+        let mut rng = StdRng::seed_from_u64(0x9E37_79B9_7F4A_7C15);
+        Self {
+            stones: (0..MAX_BOARD_SIZE * MAX_BOARD_SIZE)
+                .map(|_| [rng.gen(), rng.gen()])
+                .collect(),
+            maximizing: rng.gen(),
+            o_is_black: rng.gen(),
+        }
+    }
+}
+
+/// The key toggled by placing or removing `player` at `pos`.
+pub fn stone_key(pos: &Position, player: Player) -> u64 {
+    let index = pos.row * MAX_BOARD_SIZE + pos.col;
+    ZOBRIST.stones[index][player as usize]
+}
+
+/// Key for a search node: the board hash plus who is to move and who plays black, both of
+/// which change the score of an otherwise identical board.
+pub fn node_key(board_hash: u64, is_maximizing: bool, init_player: Player) -> u64 {
+    let mut key = board_hash;
+    if is_maximizing {
+        key ^= ZOBRIST.maximizing;
+    }
+    if init_player == Player::O {
+        key ^= ZOBRIST.o_is_black;
+    }
+    key
+}
+
+/// How a stored score relates to the true minimax value of the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The search failed high; the true value is at least the score.
+    Lower,
+    /// The search failed low; the true value is at most the score.
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TtEntry {
+    pub key: u64,
+    pub depth: i32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<Position>,
+}
+
+impl TtEntry {
+    /// The stored score, if it is deep enough and settles the `alpha..beta` window.
+    pub fn cutoff(&self, depth: i32, alpha: i32, beta: i32) -> Option<i32> {
+        if self.depth < depth {
+            return None;
+        }
+        match self.bound {
+            Bound::Exact => Some(self.score),
+            Bound::Lower if self.score >= beta => Some(self.score),
+            Bound::Upper if self.score <= alpha => Some(self.score),
+            _ => None,
+        }
+    }
+}
+
+/// Fixed-size, always-indexed table of search results, shared by the threads of a search
+/// without locks. Each slot holds the entry packed into one word and the key XORed with it,
+/// so a slot torn by two threads writing at once reads as a miss rather than a wrong entry.
+/// It is kept per room so the bot can reuse what it learned on earlier turns.
+#[derive(Debug, Default)]
+pub struct TranspositionTable {
+    slots: OnceLock<Box<[Slot]>>,
+}
+
+#[derive(Debug, Default)]
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+/// Set in every packed entry so an empty slot never matches.
+const OCCUPIED: u64 = 1 << 63;
+const HAS_MOVE: u64 = 1 << 34;
+
+impl TranspositionTable {
+    pub fn get(&self, key: u64) -> Option<TtEntry> {
+        let slot = &self.slots.get()?[Self::index(key)];
+        let data = slot.data.load(Ordering::Relaxed);
+        let check = slot.check.load(Ordering::Relaxed);
+        (data & OCCUPIED != 0 && check ^ data == key).then(|| unpack(key, data))
+    }
+
+    /// Stores `entry`, replacing a shallower result for the same slot or any result for
+    /// another position.
+    pub fn store(&self, entry: TtEntry) {
+        let slots = self.slots.get_or_init(|| {
+            (0..TABLE_SIZE)
+                .map(|_| Slot::default())
+                .collect::<Vec<_>>()
+                .into_boxed_slice()
+        });
+        if self
+            .get(entry.key)
+            .is_some_and(|existing| existing.depth > entry.depth)
+        {
+            return;
+        }
+        let slot = &slots[Self::index(entry.key)];
+        let data = pack(&entry);
+        slot.data.store(data, Ordering::Relaxed);
+        slot.check.store(entry.key ^ data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots.get().into_iter().flatten() {
+            slot.data.store(0, Ordering::Relaxed);
+            slot.check.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn index(key: u64) -> usize {
+        key as usize & (TABLE_SIZE - 1)
+    }
+}
+
+/// Score in bits 0-15, depth in 16-31, bound in 32-33, then the best move's presence, column
+/// and row. Scores and depths are far inside `i16`.
+fn pack(entry: &TtEntry) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+    let best_move = entry.best_move.map_or(0, |pos| {
+        HAS_MOVE | (pos.col as u64) << 35 | (pos.row as u64) << 41
+    });
+    OCCUPIED
+        | u64::from(entry.score as i16 as u16)
+        | u64::from(entry.depth as i16 as u16) << 16
+        | bound << 32
+        | best_move
+}
+
+fn unpack(key: u64, data: u64) -> TtEntry {
+    TtEntry {
+        key,
+        score: i32::from(data as u16 as i16),
+        depth: i32::from((data >> 16) as u16 as i16),
+        bound: match (data >> 32) & 3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        },
+        best_move: (data & HAS_MOVE != 0).then(|| {
+            Position::new(
+                ((data >> 35) & 0x3f) as usize,
+                ((data >> 41) & 0x3f) as usize,
+            )
+        }),
+    }
+}
@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod tests {
+    use backend::analysis::{analyse, MoveClass};
+    use backend::models::{BotLevel, Game, GameType, Move, Player, Position, WIN_SCORE};
+    use std::sync::atomic::AtomicBool;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_classify_by_swing() {
+        assert_eq!(MoveClass::classify(300, 300), MoveClass::Best);
+        assert_eq!(MoveClass::classify(300, 280), MoveClass::Good);
+        assert_eq!(MoveClass::classify(300, 200), MoveClass::Inaccuracy);
+        assert_eq!(MoveClass::classify(300, 100), MoveClass::Mistake);
+        assert_eq!(MoveClass::classify(300, -100), MoveClass::Blunder);
+        assert_eq!(MoveClass::classify(0, -WIN_SCORE), MoveClass::Blunder);
+        assert_eq!(MoveClass::classify(WIN_SCORE, 400), MoveClass::MissedWin);
+        assert_eq!(
+            MoveClass::classify(WIN_SCORE + 2, WIN_SCORE),
+            MoveClass::Good
+        );
+    }
+
+    #[test]
+    fn test_analysis_flags_missed_win() {
+        let mut game = Game::new(Uuid::new_v4(), Player::X, GameType::Normal, 15);
+        game.x = Some(Uuid::new_v4());
+        game.o = Some(Uuid::new_v4());
+        let moves = [
+            (7, 7),
+            (0, 0),
+            (8, 7),
+            (0, 1),
+            (9, 7),
+            (0, 2),
+            (10, 7),
+            (6, 7),
+            (14, 14),
+        ];
+        for (col, row) in moves {
+            game.play(&Move::new(game.next_player, Position::new(col, row)))
+                .unwrap();
+        }
+
+        let settings = BotLevel::Casual.settings();
+        let analysis = analyse(&game, &settings, &AtomicBool::new(false)).unwrap();
+        assert_eq!(analysis.len(), moves.len());
+        assert_eq!(analysis[0].turn, 1);
+        // Making the open four wins outright.
+        assert_eq!(analysis[6].class, MoveClass::Best);
+        let missed = &analysis[8];
+        assert_eq!(missed.turn, 9);
+        assert_eq!(missed.class, MoveClass::MissedWin);
+        assert_eq!(missed.best, Position::new(11, 7));
+        assert!(missed.swing > 0);
+        assert!(missed
+            .to_notation(game.board_size)
+            .starts_with("9. Xo1 missed win (best l8"));
+    }
+}
@@ -3,17 +3,27 @@ mod common;
 #[cfg(test)]
 mod tests {
     // use http_body_util::BodyExt;
-    use crate::common::{self, generate_access_token};
+    use crate::common::{self, access_token_for, generate_access_token};
     use axum::{
         body::Body,
         http::{Request, StatusCode},
     };
+    use backend::api::AppOptions;
+    use std::time::Duration;
 
     use backend::{
-        api::{GamePayload, GameResponse},
-        models::GameType,
+        analysis::MoveAnalysis,
+        api::{
+            EvaluatePayload, EvaluateResponse, GamePayload, GameResponse, HintPayload,
+            HintResponse, HistoryPage, ImportResponse,
+        },
+        db::Db,
+        models::{Game, GameResult, GameStatus, GameType, Move, Player, Position},
     };
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
     use tower::ServiceExt;
+    use uuid::Uuid;
 
     #[tokio::test]
     async fn test_healthcheck() {
@@ -46,6 +56,12 @@ mod tests {
 
         let payload = GamePayload {
             game_type: GameType::Bot,
+            board_size: None,
+            rule_set: None,
+            opening: None,
+            bot_level: None,
+            colour: None,
+            seed: None,
         };
         let client = reqwest::Client::new();
         let token = generate_access_token();
@@ -60,4 +76,627 @@ mod tests {
         let res = res.unwrap().json::<GameResponse>().await;
         assert!(res.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_create_game_with_invalid_board_size() {
+        let app = common::spawn_router().await;
+        assert!(app.is_ok());
+        let (_pool, router, listener) = app.unwrap();
+
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let payload = GamePayload {
+            game_type: GameType::Bot,
+            board_size: Some(50),
+            rule_set: None,
+            opening: None,
+            bot_level: None,
+            colour: None,
+            seed: None,
+        };
+        let client = reqwest::Client::new();
+        let token = generate_access_token();
+        let res = client
+            .post(format!("http://{addr}/api/games"))
+            .bearer_auth(token)
+            .json(&payload)
+            .send()
+            .await;
+
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_create_game_with_seed_outside_debug_mode() {
+        let app = common::spawn_router().await;
+        assert!(app.is_ok());
+        let (_pool, router, listener) = app.unwrap();
+
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let payload = GamePayload {
+            game_type: GameType::Bot,
+            board_size: None,
+            rule_set: None,
+            opening: None,
+            bot_level: None,
+            colour: None,
+            seed: Some(42),
+        };
+        let client = reqwest::Client::new();
+        let token = generate_access_token();
+        let res = client
+            .post(format!("http://{addr}/api/games"))
+            .bearer_auth(token)
+            .json(&payload)
+            .send()
+            .await;
+
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_hints_are_limited_per_game() {
+        let app = common::spawn_router().await;
+        assert!(app.is_ok());
+        let (pool, router, listener) = app.unwrap();
+
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let token = generate_access_token();
+        let create = |game_type| {
+            let payload = GamePayload {
+                game_type,
+                board_size: None,
+                rule_set: None,
+                opening: None,
+                bot_level: None,
+                colour: None,
+                seed: None,
+            };
+            client
+                .post(format!("http://{addr}/api/games"))
+                .bearer_auth(&token)
+                .json(&payload)
+                .send()
+        };
+        let game_id = |res: serde_json::Value| {
+            let room_id: Uuid = res["room"].as_str().unwrap().parse().unwrap();
+            let pool = pool.clone();
+            async move {
+                sqlx::query_scalar::<_, Uuid>("select id from game where room_id = $1")
+                    .bind(room_id)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap()
+            }
+        };
+        let ask = |game_id: Uuid| {
+            client
+                .post(format!("http://{addr}/api/games/{game_id}/hints"))
+                .bearer_auth(&token)
+                .json(&HintPayload { count: Some(3) })
+                .send()
+        };
+
+        let res = create(GameType::Bot).await.unwrap().json().await.unwrap();
+        let bot_game = game_id(res).await;
+        for remaining in (0..10).rev() {
+            let res = ask(bot_game).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            let hint = res.json::<HintResponse>().await.unwrap();
+            assert_eq!(hint.remaining, remaining);
+            assert_eq!(hint.candidates[0].position, Position::new(7, 7));
+        }
+        let res = ask(bot_game).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Normal games come from matchmaking, so this one is stored directly.
+        let mut normal_game = Game::new(Uuid::new_v4(), Player::X, GameType::Normal, 15);
+        normal_game.x = Some(Uuid::new_v4());
+        normal_game.o = Some(Uuid::new_v4());
+        Db::new(pool.clone()).new_game(&normal_game).await.unwrap();
+        let res = ask(normal_game.id).await.unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_analysis_waits_for_the_result() {
+        let app = common::spawn_router().await;
+        assert!(app.is_ok());
+        let (pool, router, listener) = app.unwrap();
+
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let payload = GamePayload {
+            game_type: GameType::Bot,
+            board_size: None,
+            rule_set: None,
+            opening: None,
+            bot_level: None,
+            colour: None,
+            seed: None,
+        };
+        let client = reqwest::Client::new();
+        let token = generate_access_token();
+        let res = client
+            .post(format!("http://{addr}/api/games"))
+            .bearer_auth(&token)
+            .json(&payload)
+            .send()
+            .await
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap();
+        let room_id: Uuid = res["room"].as_str().unwrap().parse().unwrap();
+        let game_id = sqlx::query_scalar::<_, Uuid>("select id from game where room_id = $1")
+            .bind(room_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        let res = client
+            .get(format!("http://{addr}/api/games/{game_id}/analysis"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+
+        let res = client
+            .get(format!(
+                "http://{addr}/api/games/{}/analysis",
+                Uuid::new_v4()
+            ))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_finds_the_winning_move() {
+        let app = common::spawn_router().await;
+        assert!(app.is_ok());
+        let (_pool, router, listener) = app.unwrap();
+
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let token = generate_access_token();
+        let moves = [
+            (7, 7),
+            (0, 0),
+            (8, 7),
+            (0, 1),
+            (9, 7),
+            (0, 2),
+            (10, 7),
+            (14, 14),
+        ]
+        .map(|(col, row)| Position::new(col, row));
+        let payload = EvaluatePayload {
+            moves: Some(moves.to_vec()),
+            ..Default::default()
+        };
+        let res = client
+            .post(format!("http://{addr}/api/evaluate"))
+            .bearer_auth(&token)
+            .json(&payload)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = res.json::<EvaluateResponse>().await.unwrap();
+        assert_eq!(res.next_player, Player::X);
+        assert!(res.evaluation > 0);
+        assert!(res.forced_win.is_some());
+        assert!(matches!(
+            res.best_move,
+            Some(Position {
+                col: 6 | 11,
+                row: 7
+            })
+        ));
+
+        let payload = EvaluatePayload {
+            moves: Some(moves.to_vec()),
+            board: Some("x".repeat(15)),
+            ..Default::default()
+        };
+        let res = client
+            .post(format!("http://{addr}/api/evaluate"))
+            .bearer_auth(&token)
+            .json(&payload)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_import_then_export() {
+        let app = common::spawn_router().await;
+        assert!(app.is_ok());
+        let (pool, router, listener) = app.unwrap();
+
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let token = generate_access_token();
+        let res = client
+            .post(format!("http://{addr}/api/games/import?format=sgf"))
+            .bearer_auth(&token)
+            .body("(;GM[4]FF[4]SZ[15];B[hh];W[ii];B[hi])")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let imported = res.json::<ImportResponse>().await.unwrap();
+        assert_eq!(imported.games.len(), 1);
+
+        let game_id = imported.games[0];
+        let res = client
+            .get(format!(
+                "http://{addr}/api/games/{game_id}/export?format=psq"
+            ))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.text().await.unwrap(),
+            "Piskvorky 15x15, 11:11, 0\n8,8,0\n9,9,0\n8,9,0\n"
+        );
+        let res = client
+            .get(format!("http://{addr}/api/games/{game_id}/export"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.text().await.unwrap(), "Xh8 i7 h7");
+
+        let res = client
+            .post(format!("http://{addr}/api/games/import?format=sgf"))
+            .bearer_auth(&token)
+            .body("(;GM[4]FF[4]SZ[19];B[aa];W[ab];B[ba];W[bb];B[ca];W[cb];B[da];W[db];B[ea])")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let won = res.json::<ImportResponse>().await.unwrap().games[0];
+        // RIF only holds 15x15 games.
+        let res = client
+            .get(format!("http://{addr}/api/games/{won}/export?format=rif"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        // Imported wins are nobody's, so they do not feed the opening book.
+        let won_games = Db::new(pool).get_won_games(1).await.unwrap();
+        assert!(won_games.iter().all(|game| game.id != won));
+
+        let res = client
+            .post(format!("http://{addr}/api/games/import?format=sgf"))
+            .bearer_auth(&token)
+            .body("(;GM[4]FF[4]SZ[15];B[hh];W[hh])")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_history_pages_and_replay() {
+        let app = common::spawn_router().await;
+        assert!(app.is_ok());
+        let (_pool, router, listener) = app.unwrap();
+
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let user_id = Uuid::new_v4();
+        let token = access_token_for(user_id);
+        let res = client
+            .post(format!("http://{addr}/api/games/import?format=sgf"))
+            .bearer_auth(&token)
+            .body("(;GM[4]SZ[15];B[hh];W[ii])(;GM[4]SZ[15];B[aa])(;GM[4]SZ[15];B[bb];W[cc];B[dd])")
+            .send()
+            .await
+            .unwrap();
+        let imported = res.json::<ImportResponse>().await.unwrap();
+        assert_eq!(imported.games.len(), 3);
+
+        let res = client
+            .get(format!("http://{addr}/api/games/{}", imported.games[2]))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let game = res.json::<Game>().await.unwrap();
+        let moves: Vec<Position> = game.moves.iter().map(|mv| mv.position).collect();
+        assert_eq!(
+            moves,
+            vec![
+                Position::new(1, 1),
+                Position::new(2, 2),
+                Position::new(3, 3)
+            ]
+        );
+
+        let res = client
+            .get(format!(
+                "http://{addr}/api/users/{user_id}/games?page=2&page_size=2"
+            ))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let history = res.json::<HistoryPage>().await.unwrap();
+        assert_eq!(history.total, 3);
+        assert_eq!(history.games.len(), 1);
+        assert_eq!(history.games[0].game_type, GameType::Private);
+        // The uploader did not play imported games.
+        assert_eq!(history.games[0].colour, None);
+        assert_eq!(history.games[0].opponent, None);
+
+        let res = client
+            .get(format!("http://{addr}/api/users/{user_id}/games?page=0"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let res = client
+            .get(format!("http://{addr}/api/games/{}", Uuid::new_v4()))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_join_private_game_with_invite_code() {
+        let app = common::spawn_router().await;
+        assert!(app.is_ok());
+        let (_pool, router, listener) = app.unwrap();
+
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let payload = GamePayload {
+            game_type: GameType::Private,
+            board_size: Some(19),
+            rule_set: None,
+            opening: None,
+            bot_level: None,
+            colour: Some(Player::O),
+            seed: None,
+        };
+        let private = client
+            .post(format!("http://{addr}/api/games"))
+            .bearer_auth(generate_access_token())
+            .json(&payload)
+            .send()
+            .await
+            .unwrap()
+            .json::<GameResponse>()
+            .await
+            .unwrap();
+        let invite_code = private.invite_code.unwrap();
+
+        let join = |token: String, code: String| {
+            client
+                .post(format!("http://{addr}/api/invites/{code}"))
+                .bearer_auth(token)
+                .send()
+        };
+        let guest = generate_access_token();
+        let res = join(guest.clone(), invite_code.to_lowercase())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.json::<GameResponse>().await.unwrap().room, private.room);
+
+        // Joining again is harmless; a third player finds the game full.
+        let res = join(guest, invite_code.clone()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = join(generate_access_token(), invite_code).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let res = join(generate_access_token(), "NOSUCHCODE".into())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_leaving_a_game_under_way_loses_on_time() {
+        let app = common::spawn_router_with_options(AppOptions {
+            abandon_grace: Duration::from_secs(1),
+            ..AppOptions::default()
+        })
+        .await;
+        assert!(app.is_ok());
+        let (pool, router, listener) = app.unwrap();
+
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let token = generate_access_token();
+        let payload = GamePayload {
+            game_type: GameType::Bot,
+            board_size: None,
+            rule_set: None,
+            opening: None,
+            bot_level: None,
+            colour: None,
+            seed: None,
+        };
+        let res = reqwest::Client::new()
+            .post(format!("http://{addr}/api/games"))
+            .bearer_auth(&token)
+            .json(&payload)
+            .send()
+            .await
+            .unwrap()
+            .json::<GameResponse>()
+            .await
+            .unwrap();
+        let game_id = sqlx::query_scalar::<_, Uuid>("select id from game where room_id = $1")
+            .bind(res.room)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let db = Db::new(pool.clone());
+        db.insert_move(&game_id, &Move::new(Player::X, Position::new(7, 7)), 1)
+            .await
+            .unwrap();
+
+        let (mut socket, _) =
+            tokio_tungstenite::connect_async(format!("ws://{addr}/ws/rooms/{}", res.room))
+                .await
+                .unwrap();
+        socket.send(WsMessage::Text(token)).await.unwrap();
+        socket.next().await.unwrap().unwrap();
+        socket.close(None).await.unwrap();
+
+        // Leaving the bot is not losing yet: the player may be reloading the page.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(db.get_game(&game_id).await.unwrap().result, None);
+        let mut result = None;
+        for _ in 0..50 {
+            result = db.get_game(&game_id).await.unwrap().result;
+            if result.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(result, Some(GameResult::XTimedOut));
+    }
+
+    #[tokio::test]
+    async fn test_first_player_to_leave_loses_on_time() {
+        let app = common::spawn_router().await;
+        assert!(app.is_ok());
+        let (pool, router, listener) = app.unwrap();
+
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let (x, o) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut game = Game::new(Uuid::new_v4(), Player::X, GameType::Private, 15);
+        game.x = Some(x);
+        game.o = Some(o);
+        game.status = GameStatus::Playing;
+        let db = Db::new(pool);
+        db.new_game(&game).await.unwrap();
+        db.insert_move(&game.id, &Move::new(Player::X, Position::new(7, 7)), 1)
+            .await
+            .unwrap();
+
+        let mut sockets = vec![];
+        for user in [x, o] {
+            let (mut socket, _) =
+                tokio_tungstenite::connect_async(format!("ws://{addr}/ws/rooms/{}", game.room_id))
+                    .await
+                    .unwrap();
+            socket
+                .send(WsMessage::Text(access_token_for(user)))
+                .await
+                .unwrap();
+            socket.next().await.unwrap().unwrap();
+            sockets.push(socket);
+        }
+        // X leaves while O is on move, then O leaves too.
+        for mut socket in sockets {
+            socket.close(None).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+
+        let mut result = None;
+        for _ in 0..50 {
+            result = db.get_game(&game.id).await.unwrap().result;
+            if result.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(result, Some(GameResult::XTimedOut));
+    }
+
+    #[tokio::test]
+    async fn test_moves_past_ply_255_are_stored() {
+        let app = common::spawn_router().await;
+        assert!(app.is_ok());
+        let (pool, _router, _listener) = app.unwrap();
+
+        let db = Db::new(pool);
+        let mut game = Game::new(Uuid::new_v4(), Player::X, GameType::Normal, 30);
+        game.x = Some(Uuid::new_v4());
+        game.o = Some(Uuid::new_v4());
+        db.new_game(&game).await.unwrap();
+        // A full 30x30 board.
+        for turn in [256, 900] {
+            let mv = Move::new(Player::X, Position::new(turn % 30, turn / 30 - 1));
+            db.insert_move(&game.id, &mv, turn).await.unwrap();
+        }
+        let mv = Move::new(Player::O, Position::new(0, 0));
+        assert!(db.insert_move(&game.id, &mv, 901).await.is_err());
+        assert!(db
+            .insert_hint(&game.id, &game.x.unwrap(), 900, 1)
+            .await
+            .unwrap());
+        let analysis = MoveAnalysis::new(900, mv, Position::new(1, 0), 0, 0);
+        db.insert_analysis(&game.id, &[analysis]).await.unwrap();
+        assert_eq!(db.get_analysis(&game.id).await.unwrap()[0].turn, 900);
+    }
 }
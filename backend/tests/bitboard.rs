@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+    use backend::bitboard::{BitBoard, Pattern};
+    use backend::models::{Player, Position};
+
+    #[test]
+    fn test_lines_through_round_trip() {
+        for size in [9, 15, 30] {
+            let bits = BitBoard::new(size);
+            for row in 0..size {
+                for col in 0..size {
+                    for (line, bit) in bits.lines_through(&Position::new(col, row)) {
+                        let pos = bits.position(line, bit);
+                        assert_eq!((pos.col, pos.row), (col, row), "size {size}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_through_and_five() {
+        let mut bits = BitBoard::new(15);
+        for i in 2..7 {
+            bits.place(&Position::new(i, 12 - i), Player::X);
+        }
+        bits.place(&Position::new(1, 11), Player::O);
+        bits.place(&Position::new(7, 5), Player::O);
+
+        let [_, _, _, (line, bit)] = bits.lines_through(&Position::new(4, 8));
+        assert_eq!(bits.run_through(line, bit, Player::X), (2, 6, true));
+        assert!(bits.has_five(line, Player::X, |len, _| len == 5));
+        assert!(!bits.has_five(line, Player::X, |len, blocked| len == 5 && !blocked));
+
+        bits.remove(&Position::new(4, 8));
+        assert!(!bits.has_five(line, Player::X, |len, _| len >= 5));
+    }
+
+    #[test]
+    fn test_winning_threat_skips_patterns_longer_than_a_diagonal_reach() {
+        let mut bits = BitBoard::new(30);
+        for i in [0, 1, 3] {
+            bits.place(&Position::new(i, i), Player::O);
+            bits.place(&Position::new(i, 4 - i), Player::O);
+        }
+        let patterns = [
+            Pattern::compile("_o_o_o_o_o_o_o_", 400),
+            Pattern::compile("oo_o", 400),
+            Pattern::compile("o_oo", 400),
+        ];
+        // Both diagonals through (2, 2) hold a short pattern; the long one never fits so near
+        // the corner.
+        let centre = Position::new(2, 2);
+        assert_eq!(bits.winning_threat(&centre, Player::O, &patterns[..1]), 0);
+        assert_eq!(bits.winning_threat(&centre, Player::O, &patterns), 3);
+    }
+}
@@ -0,0 +1,131 @@
+#[cfg(test)]
+mod tests {
+    use backend::book::{BookLine, OpeningBook};
+    use backend::models::{Game, GameResult, GameType, Move, Player, Position, RuleSet};
+    use uuid::Uuid;
+
+    fn game_with(moves: &[(usize, usize)]) -> Game {
+        let mut game = Game::new(Uuid::new_v4(), Player::X, GameType::Bot, 15);
+        game.x = Some(Uuid::new_v4());
+        game.o = Some(Uuid::nil());
+        for &(col, row) in moves {
+            game.play(&Move::new(game.next_player, Position::new(col, row)))
+                .unwrap();
+        }
+        game
+    }
+
+    fn line(moves: &[(usize, usize)]) -> BookLine {
+        BookLine {
+            board_size: 15,
+            rule_set: RuleSet::Freestyle,
+            init_player: Player::X,
+            moves: moves
+                .iter()
+                .map(|&(col, row)| Position::new(col, row))
+                .collect(),
+            winner: None,
+            weight: 1,
+            game_id: None,
+        }
+    }
+
+    #[test]
+    fn test_lookup_matches_symmetric_positions() {
+        let mut book = OpeningBook::default();
+        book.add_line(line(&[(7, 7), (8, 7), (9, 9)])).unwrap();
+
+        // The booked position turned half a turn.
+        let game = game_with(&[(7, 7), (6, 7)]);
+        let reply = book.lookup(&game).unwrap();
+        assert!(
+            [Position::new(5, 5), Position::new(5, 9)].contains(&reply),
+            "{reply:?}"
+        );
+        assert!(book.lookup(&game_with(&[(7, 7), (9, 9)])).is_none());
+    }
+
+    #[test]
+    fn test_book_stops_after_max_plies() {
+        let mut book = OpeningBook::new(1);
+        book.add_line(line(&[(7, 7), (8, 8)])).unwrap();
+        assert_eq!(book.lookup(&game_with(&[])), Some(Position::new(7, 7)));
+        assert!(book.lookup(&game_with(&[(7, 7)])).is_none());
+    }
+
+    #[test]
+    fn test_harvest_books_only_winner_moves() {
+        let mut game = game_with(&[(7, 7), (8, 8), (9, 7)]);
+        game.finish(GameResult::OWins);
+        let mut book = OpeningBook::default();
+        assert!(book.harvest(&game).unwrap());
+
+        assert!(book.lookup(&game_with(&[])).is_none());
+        let reply = book.lookup(&game_with(&[(7, 7)])).unwrap();
+        assert_eq!((reply.col.abs_diff(7), reply.row.abs_diff(7)), (1, 1));
+        assert!(book.lookup(&game_with(&[(7, 7), (8, 8)])).is_none());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut book = OpeningBook::new(4);
+        book.add_line(line(&[(7, 7), (8, 8), (9, 7)])).unwrap();
+        let path = std::env::temp_dir().join(format!("book-{}.json", Uuid::new_v4()));
+        book.save(&path).unwrap();
+        let loaded = OpeningBook::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.max_plies, 4);
+        assert_eq!(loaded.len(), book.len());
+    }
+
+    #[test]
+    fn test_lines_off_the_board_are_refused() {
+        let mut book = OpeningBook::default();
+        assert!(book.add_line(line(&[(7, 7), (15, 3)])).is_err());
+        assert!(book.add_line(line(&[(7, 7), (7, 7)])).is_err());
+        assert!(book.is_empty());
+
+        let path = std::env::temp_dir().join(format!("book-{}.json", Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"{"lines": [{"board_size": 9, "moves": [{"col": 4, "row": 20}]}]}"#,
+        )
+        .unwrap();
+        let loaded = OpeningBook::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.is_err());
+    }
+
+    #[test]
+    fn test_lines_are_kept_apart_by_who_moved_first() {
+        let mut book = OpeningBook::default();
+        let mut o_first = line(&[(7, 7), (8, 8)]);
+        o_first.init_player = Player::O;
+        book.add_line(o_first).unwrap();
+        assert!(book.lookup(&game_with(&[(7, 7)])).is_none());
+
+        let mut game = Game::new(Uuid::new_v4(), Player::O, GameType::Bot, 15);
+        game.x = Some(Uuid::nil());
+        game.o = Some(Uuid::new_v4());
+        game.play(&Move::new(Player::O, Position::new(7, 7)))
+            .unwrap();
+        assert!(book.lookup(&game).is_some());
+    }
+
+    #[test]
+    fn test_harvesting_a_game_again_changes_nothing() {
+        let mut game = game_with(&[(7, 7), (8, 8), (9, 7)]);
+        game.finish(GameResult::XWins);
+        let mut book = OpeningBook::default();
+        assert!(book.harvest(&game).unwrap());
+
+        let path = std::env::temp_dir().join(format!("book-{}.json", Uuid::new_v4()));
+        book.save(&path).unwrap();
+        let mut loaded = OpeningBook::load(&path).unwrap();
+        assert!(!loaded.harvest(&game).unwrap());
+        loaded.save(&path).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.matches("\"game_id\"").count(), 1);
+    }
+}
@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod tests {
+    use backend::gomocup::{Engine, Reply};
+    use backend::models::{BotLevel, Player, RuleSet};
+
+    fn line(reply: Reply) -> String {
+        match reply {
+            Reply::Line(line) => line,
+            reply => panic!("expected a line, got {reply:?}"),
+        }
+    }
+
+    #[test]
+    fn test_start_begin_and_turn() {
+        let mut engine = Engine::new(BotLevel::Strong);
+        assert_eq!(line(engine.handle("START 15")), "OK");
+        assert_eq!(engine.handle("INFO timeout_turn 300"), Reply::Silent);
+        assert_eq!(engine.handle("INFO rule 1"), Reply::Silent);
+        assert_eq!(line(engine.handle("BEGIN")), "7,7");
+
+        let reply = line(engine.handle("TURN 8,8"));
+        let game = engine.game().unwrap();
+        assert_eq!(game.rule_set, RuleSet::Standard);
+        assert_eq!(game.moves.len(), 3);
+        let last = game.moves.last().unwrap();
+        assert_eq!(last.player, Player::X);
+        assert_eq!(
+            reply,
+            format!("{},{}", last.position.col, last.position.row)
+        );
+
+        assert!(line(engine.handle("TURN 8,8")).starts_with("ERROR"));
+        assert!(line(engine.handle("START 100")).starts_with("ERROR"));
+        assert!(line(engine.handle("FOO")).starts_with("UNKNOWN"));
+        assert_eq!(engine.handle("END"), Reply::End);
+    }
+
+    #[test]
+    fn test_board_blocks_opponent_four() {
+        let mut engine = Engine::new(BotLevel::Strong);
+        engine.handle("START 15");
+        engine.handle("INFO timeout_turn 300");
+        assert_eq!(engine.handle("BOARD"), Reply::Silent);
+        // The opponent played first and has four in a row; the engine plays O.
+        for stone in [
+            "0,0,2", "7,7,1", "1,0,2", "9,9,1", "2,0,2", "11,7,1", "3,0,2",
+        ] {
+            assert_eq!(engine.handle(stone), Reply::Silent);
+        }
+        assert_eq!(line(engine.handle("DONE")), "4,0");
+        let game = engine.game().unwrap();
+        assert_eq!(game.moves.last().unwrap().player, Player::O);
+        assert_eq!(game.board[0][4], Some(Player::O));
+    }
+}
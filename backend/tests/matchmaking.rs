@@ -0,0 +1,109 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{self, generate_access_token};
+    use backend::matchmaking::{band, MatchEvent, MatchSettings, Queue, Ticket};
+    use backend::models::{Opening, RuleSet};
+    use futures::{SinkExt, StreamExt};
+    use std::time::{Duration, Instant};
+    use tokio_tungstenite::tungstenite::Message;
+    use uuid::Uuid;
+
+    const SETTINGS: MatchSettings = MatchSettings {
+        board_size: 15,
+        rule_set: RuleSet::Standard,
+        opening: Opening::Free,
+    };
+
+    #[test]
+    fn test_band_widens_while_waiting() {
+        assert_eq!(band(Duration::ZERO), 100.0);
+        assert_eq!(band(Duration::from_secs(10)), 350.0);
+        assert_eq!(band(Duration::from_secs(3600)), 1000.0);
+    }
+
+    #[test]
+    fn test_pair_closest_rating_in_reach() {
+        let mut queue = Queue::default();
+        let mut receivers = vec![];
+        for rating in [1500.0, 1900.0, 1560.0, 1530.0] {
+            let (ticket, matched) = Ticket::new(Uuid::new_v4(), rating, SETTINGS);
+            receivers.push(matched);
+            queue.push(ticket);
+        }
+        let pairs = queue.pair(Instant::now());
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].0.rating, pairs[0].1.rating), (1500.0, 1530.0));
+        assert_eq!(queue.len(), 2);
+
+        // Nobody else is in reach until the bands have widened.
+        assert!(queue.pair(Instant::now()).is_empty());
+        let pairs = queue.pair(Instant::now() + Duration::from_secs(20));
+        assert_eq!(pairs.len(), 1);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_pair_only_same_settings_and_present_players() {
+        let mut queue = Queue::default();
+        let (first, _first) = Ticket::new(Uuid::new_v4(), 1500.0, SETTINGS);
+        let other_settings = MatchSettings {
+            board_size: 19,
+            ..SETTINGS
+        };
+        let (second, _second) = Ticket::new(Uuid::new_v4(), 1500.0, other_settings);
+        let (third, third_matched) = Ticket::new(Uuid::new_v4(), 1500.0, SETTINGS);
+        queue.push(first);
+        queue.push(second);
+        queue.push(third);
+        drop(third_matched);
+        assert!(queue.pair(Instant::now()).is_empty());
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_matchmaking_websocket_pairs_two_players() {
+        let app = common::spawn_router().await;
+        assert!(app.is_ok());
+        let (_pool, router, listener) = app.unwrap();
+
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let mut sockets = vec![];
+        for _ in 0..2 {
+            let (mut socket, _) =
+                tokio_tungstenite::connect_async(format!("ws://{addr}/ws/matchmaking"))
+                    .await
+                    .unwrap();
+            socket
+                .send(Message::Text(generate_access_token()))
+                .await
+                .unwrap();
+            socket
+                .send(Message::Text(serde_json::to_string(&SETTINGS).unwrap()))
+                .await
+                .unwrap();
+            sockets.push(socket);
+        }
+
+        let mut matches = vec![];
+        for socket in &mut sockets {
+            let mut events = vec![];
+            while let Some(Ok(Message::Text(text))) = socket.next().await {
+                events.push(serde_json::from_str::<MatchEvent>(&text).unwrap());
+            }
+            assert!(matches!(events[0], MatchEvent::Queued { rating } if rating == 1500.0));
+            match events[1] {
+                MatchEvent::Matched { room, colour } => matches.push((room, colour)),
+                _ => panic!("expected a match"),
+            }
+        }
+        assert_eq!(matches[0].0, matches[1].0);
+        assert_ne!(matches[0].1, matches[1].1);
+    }
+}
@@ -0,0 +1,347 @@
+#[cfg(test)]
+mod tests {
+    use backend::models::{
+        parse_moves, BotLevel, BotSettings, ForbiddenMove, Game, GameType, Move, Opening,
+        OpeningStep, Player, Position, RuleSet,
+    };
+    use backend::transposition::TranspositionTable;
+    use std::sync::atomic::AtomicBool;
+    use std::time::{Duration, Instant};
+    use uuid::Uuid;
+
+    fn new_game(rule_set: RuleSet) -> Game {
+        let mut game = Game::new(Uuid::new_v4(), Player::X, GameType::Normal, 15);
+        game.rule_set = rule_set;
+        game.x = Some(Uuid::new_v4());
+        game.o = Some(Uuid::new_v4());
+        game
+    }
+
+    /// Plays `x` and `o` stones alternately, starting with X. `o` may be shorter than `x`.
+    fn play_all(game: &mut Game, x: &[(usize, usize)], o: &[(usize, usize)]) {
+        for (i, &(col, row)) in x.iter().enumerate() {
+            game.play(&Move::new(Player::X, Position::new(col, row)))
+                .unwrap();
+            if let Some(&(col, row)) = o.get(i) {
+                game.play(&Move::new(Player::O, Position::new(col, row)))
+                    .unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_colour_swapped_position_evaluates_to_negated_score() {
+        let settings = BotLevel::Strong.settings();
+        let first = [(5, 7), (5, 3), (6, 7), (6, 3), (7, 7), (7, 3)];
+        // O's score: the evaluation is from the side to move.
+        let o_score = |init_player: Player| {
+            let mut game = Game::new(Uuid::new_v4(), init_player, GameType::Normal, 15);
+            game.x = Some(Uuid::new_v4());
+            game.o = Some(Uuid::new_v4());
+            let mut player = init_player;
+            for &(col, row) in &first {
+                game.play(&Move::new(player, Position::new(col, row)))
+                    .unwrap();
+                player = player.opponent();
+            }
+            let (score, _) = game.static_evaluation(&settings);
+            match game.next_player {
+                Player::O => score,
+                Player::X => -score,
+            }
+        };
+        let score = o_score(Player::X);
+        assert_ne!(score, 0);
+        assert_eq!(score, -o_score(Player::O));
+    }
+
+    #[test]
+    fn test_overline_wins_only_in_freestyle() {
+        for (rule_set, wins) in [(RuleSet::Freestyle, true), (RuleSet::Standard, false)] {
+            let mut game = new_game(rule_set);
+            play_all(
+                &mut game,
+                &[(0, 7), (1, 7), (2, 7), (4, 7), (5, 7), (3, 7)],
+                &[(0, 0), (2, 0), (4, 0), (6, 0), (8, 0)],
+            );
+            let win = game.check_winning_move(&Position::new(3, 7)).unwrap();
+            assert_eq!(win.is_some(), wins, "{rule_set:?}");
+        }
+    }
+
+    #[test]
+    fn test_caro_five_blocked_at_both_ends_does_not_win() {
+        let mut game = new_game(RuleSet::Caro);
+        play_all(
+            &mut game,
+            &[(1, 7), (2, 7), (3, 7), (4, 7), (5, 7)],
+            &[(0, 7), (6, 7), (0, 0), (2, 0)],
+        );
+        let win = game.check_winning_move(&Position::new(5, 7)).unwrap();
+        assert!(win.is_none());
+    }
+
+    #[test]
+    fn test_renju_forbids_black_double_three() {
+        let mut game = new_game(RuleSet::Renju);
+        play_all(
+            &mut game,
+            &[(6, 7), (7, 6), (5, 7), (7, 5)],
+            &[(0, 0), (2, 0), (4, 0), (6, 0)],
+        );
+        let error = game
+            .play(&Move::new(Player::X, Position::new(7, 7)))
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ForbiddenMove>(),
+            Some(&ForbiddenMove::DoubleThree)
+        );
+    }
+
+    #[test]
+    fn test_renju_allows_white_double_three() {
+        let mut game = new_game(RuleSet::Renju);
+        play_all(
+            &mut game,
+            &[(0, 0), (2, 0), (4, 0), (6, 0), (8, 0)],
+            &[(6, 7), (7, 6), (5, 7), (7, 5), (7, 7)],
+        );
+        assert_eq!(game.moves.len(), 10);
+    }
+
+    #[test]
+    fn test_swap2_opening_steps_and_seat_swap() {
+        let mut game = new_game(RuleSet::Freestyle);
+        game.set_opening(Opening::Swap2);
+        let (opener, opponent) = (game.x, game.o);
+        assert_eq!(game.expected_actor(), opener);
+
+        play_all(&mut game, &[(7, 7), (8, 8)], &[(7, 8)]);
+        assert_eq!(game.opening_step, OpeningStep::Choose);
+        assert_eq!(game.expected_actor(), opponent);
+        assert!(game
+            .play(&Move::new(Player::O, Position::new(0, 0)))
+            .is_err());
+
+        game.extend_opening().unwrap();
+        game.play(&Move::new(Player::O, Position::new(6, 6)))
+            .unwrap();
+        game.play(&Move::new(Player::X, Position::new(9, 9)))
+            .unwrap();
+        assert_eq!(game.opening_step, OpeningStep::ChooseAfterExtend);
+        assert_eq!(game.expected_actor(), opener);
+
+        game.choose_colour(Player::O).unwrap();
+        assert_eq!(game.opening_step, OpeningStep::Done);
+        assert_eq!((game.x, game.o), (opponent, opener));
+        assert_eq!(game.expected_actor(), opener);
+    }
+
+    #[test]
+    fn test_bot_search_respects_time_budget() {
+        let mut game = new_game(RuleSet::Freestyle);
+        play_all(
+            &mut game,
+            &[(7, 7), (8, 8), (6, 8), (9, 6)],
+            &[(7, 8), (8, 7), (6, 6)],
+        );
+        let mut settings = BotLevel::Expert.settings();
+        settings.time_budget = Duration::from_millis(50);
+        let start = Instant::now();
+        let tt = TranspositionTable::default();
+        let pos = game.find_bot_move(&settings, &AtomicBool::new(false), &tt);
+        assert!(pos.is_some());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // A warm table from the previous turn still yields a move.
+        let pos = game.find_bot_move(&settings, &AtomicBool::new(false), &tt);
+        assert!(pos.is_some());
+    }
+
+    #[test]
+    fn test_bot_playing_x_blocks_four() {
+        let mut game = new_game(RuleSet::Freestyle);
+        play_all(
+            &mut game,
+            &[(7, 7), (9, 9), (11, 7), (7, 11)],
+            &[(0, 0), (1, 0), (2, 0), (3, 0)],
+        );
+        assert_eq!(game.next_player, Player::X);
+        let mut settings = BotLevel::Casual.settings();
+        settings.blunder_rate = 0.0;
+        let tt = TranspositionTable::default();
+        let pos = game.find_bot_move(&settings, &AtomicBool::new(false), &tt);
+        assert_eq!(pos, Some(Position::new(4, 0)));
+    }
+
+    #[test]
+    fn test_helper_threads_share_table() {
+        let mut game = new_game(RuleSet::Freestyle);
+        play_all(
+            &mut game,
+            &[(7, 7), (8, 8), (6, 8), (9, 6)],
+            &[(7, 8), (8, 7), (6, 6)],
+        );
+        let mut settings = BotLevel::Strong.settings();
+        settings.time_budget = Duration::from_millis(100);
+        settings.threads = 4;
+        let tt = TranspositionTable::default();
+        let start = Instant::now();
+        let pos = game.find_bot_move(&settings, &AtomicBool::new(false), &tt);
+        assert!(pos.is_some_and(|pos| game.board[pos.row][pos.col].is_none()));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(game.moves.len(), 7);
+    }
+
+    #[test]
+    fn test_same_seed_replays_same_bot_moves() {
+        let mut settings = BotLevel::Casual.settings();
+        settings.blunder_rate = 1.0;
+        let replay = |seed: i64| {
+            let mut game = new_game(RuleSet::Freestyle);
+            game.seed = seed;
+            game.play(&Move::new(Player::X, Position::new(7, 7)))
+                .unwrap();
+            let tt = TranspositionTable::default();
+            (0..6)
+                .map(|_| {
+                    let pos = game
+                        .find_bot_move(&settings, &AtomicBool::new(false), &tt)
+                        .unwrap();
+                    game.play(&Move::new(game.next_player, pos)).unwrap();
+                    pos
+                })
+                .collect::<Vec<_>>()
+        };
+        for seed in [0, 42, -7] {
+            assert_eq!(replay(seed), replay(seed));
+        }
+    }
+
+    #[test]
+    fn test_fixed_depth_search_replays_the_same_replies() {
+        let mut settings = BotLevel::Casual.settings();
+        settings.blunder_rate = 0.0;
+        // With no time at all the search would otherwise stop before its first iteration,
+        // and helpers would race for the table.
+        settings.time_budget = Duration::ZERO;
+        settings.threads = 4;
+        settings.fixed_depth = true;
+        let replay = |settings: &BotSettings| {
+            let mut game = new_game(RuleSet::Freestyle);
+            game.seed = 42;
+            game.play(&Move::new(Player::X, Position::new(7, 7)))
+                .unwrap();
+            (0..6)
+                .map(|_| {
+                    let tt = TranspositionTable::default();
+                    let pos = game
+                        .find_bot_move(settings, &AtomicBool::new(false), &tt)
+                        .unwrap();
+                    game.play(&Move::new(game.next_player, pos)).unwrap();
+                    pos
+                })
+                .collect::<Vec<_>>()
+        };
+        let replies = replay(&settings);
+        assert_eq!(replay(&settings), replies);
+        // The same replies as a search with all the time it needs.
+        let unhurried = BotSettings {
+            time_budget: Duration::from_secs(60),
+            threads: 1,
+            fixed_depth: false,
+            ..settings.clone()
+        };
+        assert_eq!(replay(&unhurried), replies);
+    }
+
+    #[test]
+    fn test_rank_moves_puts_the_win_first() {
+        let mut game = new_game(RuleSet::Freestyle);
+        play_all(
+            &mut game,
+            &[(3, 7), (4, 7), (5, 7), (6, 7)],
+            &[(3, 9), (4, 9), (5, 9), (0, 0)],
+        );
+        let settings = BotLevel::Casual.settings();
+        let tt = TranspositionTable::default();
+        let ranked = game.rank_moves(3, &settings, &AtomicBool::new(false), &tt);
+        assert_eq!(ranked.len(), 3);
+        assert!(matches!(
+            ranked[0].position,
+            Position { col: 2 | 7, row: 7 }
+        ));
+        assert!(ranked.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert_eq!(game.moves.len(), 8);
+    }
+
+    #[test]
+    fn test_game_from_board_diagram() {
+        let diagram = "
+            .........
+            .........
+            ..xxx....
+            ...o.....
+            ...o.....
+            .........
+            .........
+            .........
+            .........
+        ";
+        let mut game = Game::from_board(diagram, RuleSet::Freestyle).unwrap();
+        assert_eq!(game.board_size, 9);
+        assert_eq!(game.next_player, Player::O);
+        assert_eq!(game.board[2][4], Some(Player::X));
+        let (_, threats) = game.static_evaluation(&BotLevel::Casual.settings());
+        assert!(threats
+            .iter()
+            .any(|threat| threat.player == Player::X && threat.stones == 3));
+
+        assert!(Game::from_board("x........//", RuleSet::Freestyle).is_err());
+        assert!(Game::from_board(&"xx......./".repeat(9), RuleSet::Freestyle).is_err());
+    }
+
+    #[test]
+    fn test_notation_round_trip() {
+        assert_eq!(Position::new(7, 7).to_notation(15), "h8");
+        // Row 1 is at the bottom.
+        assert_eq!(Position::new(0, 14).to_notation(15), "a1");
+        assert_eq!(Position::new(26, 29).to_notation(30), "aa1");
+        assert_eq!(Position::parse("H8", 15).unwrap(), Position::new(7, 7));
+        assert_eq!(Position::parse("ad30", 30).unwrap(), Position::new(29, 0));
+        assert!(Position::parse("ae1", 30).is_err());
+        assert!(Position::parse("h0", 15).is_err());
+        assert!(Position::parse("h16", 15).is_err());
+        assert!(Position::parse("p1", 15).is_err());
+        assert!(Position::parse("8h", 15).is_err());
+        assert_eq!(
+            Move::new(Player::O, Position::new(14, 0)).to_notation(15),
+            "Oo15"
+        );
+        // Display and FromStr assume a 15x15 board.
+        assert_eq!(format!("{}", Position::new(7, 3)), "h12");
+        assert_eq!("h12".parse::<Position>().unwrap(), Position::new(7, 3));
+        assert!("h16".parse::<Position>().is_err());
+        assert_eq!(
+            Move::new(Player::X, Position::new(0, 14)).to_string(),
+            "Xa1"
+        );
+        assert_eq!(
+            "Xa1".parse::<Move>().unwrap().position,
+            Position::new(0, 14)
+        );
+
+        let moves = parse_moves("h8, i9 Xa1 o5", Player::O, 15).unwrap();
+        let players: Vec<Player> = moves.iter().map(|mv| mv.player).collect();
+        assert_eq!(players, [Player::O, Player::X, Player::X, Player::O]);
+        assert_eq!(moves[3].position, Position::new(14, 10));
+        assert!(parse_moves("h8 zz9", Player::X, 15).is_err());
+
+        let mut game = new_game(RuleSet::Freestyle);
+        play_all(&mut game, &[(7, 7), (8, 8)], &[(8, 7)]);
+        assert_eq!(game.notation(), "Xh8 i8 i7");
+        let replayed = parse_moves(&game.notation(), Player::O, 15).unwrap();
+        assert_eq!(replayed[2].player, Player::X);
+        assert_eq!(replayed[2].position, Position::new(8, 8));
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod tests {
+    use backend::models::BotLevel;
+    use backend::profile::{EvalProfile, EvalProfiles};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_partial_profile_keeps_default_patterns() {
+        let dir = std::env::temp_dir().join(format!("profile-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("aggressive.toml");
+        std::fs::write(&path, "name = \"aggressive\"\nattack = 1.5\n").unwrap();
+
+        let profile = EvalProfile::load(&path).unwrap();
+        assert_eq!(profile.name, "aggressive");
+        assert_eq!(profile.attack, 1.5);
+        assert_eq!(profile.defence, 1.0);
+        assert_eq!(profile.patterns, EvalProfile::default().patterns);
+        // Every pattern is matched for both colours.
+        assert_eq!(profile.compiled().len(), 2 * profile.patterns.len());
+
+        let path = dir.join("broken.json");
+        std::fs::write(
+            &path,
+            r#"{"patterns": [{"pattern": "_o?o_", "score": 10}]}"#,
+        )
+        .unwrap();
+        assert!(EvalProfile::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_winning_threats_follow_profile_scores() {
+        let default = EvalProfile::default();
+        assert!(default
+            .winning_threats()
+            .iter()
+            .all(|pattern| pattern.score >= 300));
+        assert_eq!(default.winning_threats().len(), 2 * default.patterns.len());
+
+        let dir = std::env::temp_dir().join(format!("profile-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("fours.json");
+        std::fs::write(
+            &path,
+            r#"{"patterns": [
+                {"pattern": "ooooo", "score": 1000},
+                {"pattern": "_oooo_", "score": 400},
+                {"pattern": "_ooo_", "score": 300, "count": 2}
+            ]}"#,
+        )
+        .unwrap();
+        let profile = EvalProfile::load(&path).unwrap();
+        assert_eq!(profile.patterns[0].count, 1);
+        assert_eq!(profile.patterns[2].count, 2);
+        // Only the top two tiers make double threats, so open threes no longer do.
+        assert_eq!(profile.winning_threats().len(), 4);
+        assert!(profile
+            .winning_threats()
+            .iter()
+            .all(|pattern| pattern.score >= 400));
+    }
+
+    #[test]
+    fn test_levels_fall_back_to_default_profile() {
+        let profiles = EvalProfiles::load(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("profiles/levels.toml"),
+        )
+        .unwrap();
+        assert_eq!(profiles.for_level(BotLevel::Expert).name, "aggressive");
+        assert_eq!(profiles.for_level(BotLevel::Casual).name, "defensive");
+        assert!(Arc::ptr_eq(
+            &profiles.settings(BotLevel::Strong).profile,
+            &EvalProfile::shared_default()
+        ));
+    }
+}
@@ -0,0 +1,106 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{self, generate_access_token};
+    use backend::api::{Leaderboard, RatingProfile};
+    use backend::db::Db;
+    use backend::models::{BotLevel, Game, GameResult, GameType, Player};
+    use backend::rating::{bot_rating, Rating};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_update_moves_equal_ratings_apart() {
+        let player = Rating::default();
+        let winner = player.update(&player, 1.0);
+        let loser = player.update(&player, 0.0);
+        assert!(winner.rating > 1600.0 && winner.rating < 1700.0);
+        assert!((winner.rating + loser.rating - 3000.0).abs() < 1e-6);
+        assert!(winner.deviation < player.deviation);
+        assert!(winner.is_provisional());
+
+        let drawn = player.update(&player, 0.5);
+        assert!((drawn.rating - player.rating).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_upsets_count_more() {
+        let player = Rating {
+            rating: 1500.0,
+            deviation: 80.0,
+            volatility: 0.06,
+        };
+        let strong = bot_rating(BotLevel::Expert);
+        let weak = bot_rating(BotLevel::Beginner);
+        let upset = player.update(&strong, 1.0).rating - player.rating;
+        let expected = player.update(&weak, 1.0).rating - player.rating;
+        assert!(upset > 10.0 * expected);
+        assert!(player.update(&weak, 0.0).rating < player.rating - upset);
+    }
+
+    #[tokio::test]
+    async fn test_save_result_rates_normal_games_once() {
+        let app = common::spawn_router().await;
+        assert!(app.is_ok());
+        let (pool, router, listener) = app.unwrap();
+        let db = Db::new(pool);
+
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+
+        let (x, o) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut game = Game::new(Uuid::new_v4(), Player::X, GameType::Normal, 15);
+        game.x = Some(x);
+        game.o = Some(o);
+        db.new_game(&game).await.unwrap();
+        game.finish(GameResult::OResigned);
+        db.save_result(&game).await.unwrap();
+        db.save_result(&game).await.unwrap();
+
+        let winner = db.get_rating(&x).await.unwrap().unwrap();
+        let loser = db.get_rating(&o).await.unwrap().unwrap();
+        assert_eq!((winner.games, loser.games), (1, 1));
+        assert!(winner.rating.rating > 1500.0 && loser.rating.rating < 1500.0);
+        let history = db.get_rating_history(&x, 10).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].game_id, game.id);
+        assert!((history[0].change - (winner.rating.rating - 1500.0)).abs() < 1e-9);
+
+        let client = reqwest::Client::new();
+        let token = generate_access_token();
+        let leaderboard = client
+            .get(format!("http://{addr}/api/leaderboard?page_size=10"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap()
+            .json::<Leaderboard>()
+            .await
+            .unwrap();
+        assert_eq!(leaderboard.total, 2);
+        assert_eq!(leaderboard.players[0].user_id, x);
+        assert_eq!(leaderboard.bots.len(), 4);
+        let profile = client
+            .get(format!("http://{addr}/api/users/{o}/rating"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap()
+            .json::<RatingProfile>()
+            .await
+            .unwrap();
+        assert_eq!(profile.rating.rating, loser.rating);
+        assert_eq!(profile.history.len(), 1);
+
+        let mut bot_game = Game::new(Uuid::new_v4(), Player::X, GameType::Bot, 15);
+        bot_game.x = Some(x);
+        bot_game.o = Some(Uuid::nil());
+        db.new_game(&bot_game).await.unwrap();
+        bot_game.finish(GameResult::XWins);
+        db.save_result(&bot_game).await.unwrap();
+        assert_eq!(db.get_rating(&x).await.unwrap().unwrap().games, 1);
+    }
+}
@@ -0,0 +1,81 @@
+#[cfg(test)]
+mod tests {
+    use backend::models::{Game, GameResult, GameType, Move, Player, Position, RuleSet};
+    use backend::record::{export, import, ImportOptions, RecordFormat};
+    use uuid::Uuid;
+
+    const OPTIONS: ImportOptions = ImportOptions {
+        rule_set: RuleSet::Freestyle,
+        board_size: None,
+    };
+
+    /// X wins along row 7; O answers along row 8.
+    fn won_game() -> Game {
+        let mut game = Game::new(Uuid::new_v4(), Player::X, GameType::Normal, 15);
+        game.x = Some(Uuid::new_v4());
+        game.o = Some(Uuid::new_v4());
+        for col in 3..8 {
+            game.play(&Move::new(Player::X, Position::new(col, 7)))
+                .unwrap();
+            if col < 7 {
+                game.play(&Move::new(Player::O, Position::new(col, 8)))
+                    .unwrap();
+            }
+        }
+        game.finish(GameResult::XWins);
+        game
+    }
+
+    #[test]
+    fn test_every_format_round_trips() {
+        let game = won_game();
+        for format in [RecordFormat::Psq, RecordFormat::Rif, RecordFormat::Sgf] {
+            let text = export(&game, format);
+            let imported = import(&text, format, OPTIONS).unwrap();
+            assert_eq!(imported.len(), 1, "{format:?}");
+            let imported = &imported[0];
+            assert_eq!(imported.notation(), game.notation(), "{format:?}");
+            assert_eq!(imported.result, Some(GameResult::XWins), "{format:?}");
+            assert!(imported.winner.is_some());
+        }
+    }
+
+    #[test]
+    fn test_formats_use_their_own_coordinates() {
+        let game = won_game();
+        assert!(export(&game, RecordFormat::Psq).starts_with("Piskvorky 15x15, 11:11, 0\n4,8,0\n"));
+        // RIF counts rows from the bottom.
+        assert!(export(&game, RecordFormat::Rif).contains("<move>d8 d7 e8"));
+        let sgf = export(&game, RecordFormat::Sgf);
+        assert!(sgf.starts_with("(;GM[4]FF[4]CA[UTF-8]SZ[15]RE[B+];B[dh];W[di]"));
+
+        // Only the main line of a variation is read.
+        let sgf = "(;GM[4]SZ[9]AP[x][y];B[ee](;W[ef];B[dd])(;W[aa]))(;SZ[9];B[ee])";
+        let games = import(sgf, RecordFormat::Sgf, OPTIONS).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].notation(), "Xe5 e4 d6");
+        assert_eq!(games[1].board_size, 9);
+        assert!(games[1].result.is_none());
+    }
+
+    #[test]
+    fn test_invalid_records_are_rejected() {
+        let taken_twice = "Piskvorky 15x15, 11:11, 0\n8,8,0\n8,8,0\n";
+        assert!(import(taken_twice, RecordFormat::Psq, OPTIONS).is_err());
+
+        let mut text = export(&won_game(), RecordFormat::Sgf);
+        text.insert_str(text.len() - 2, ";W[aa]");
+        assert!(import(&text, RecordFormat::Sgf, OPTIONS).is_err());
+
+        assert!(import("(;GM[1];B[aa])", RecordFormat::Sgf, OPTIONS).is_err());
+        assert!(import("<database></database>", RecordFormat::Rif, OPTIONS).is_err());
+    }
+
+    #[test]
+    fn test_sgf_colours_must_alternate() {
+        let error = import("(;GM[4]SZ[15];B[hh];B[ii])", RecordFormat::Sgf, OPTIONS).unwrap_err();
+        assert!(error.to_string().contains("move 2"), "{error}");
+        assert!(import("(;GM[4]SZ[15];W[hh])", RecordFormat::Sgf, OPTIONS).is_err());
+        assert!(import("(;GM[4]SZ[15];B[hh];W[ii])", RecordFormat::Sgf, OPTIONS).is_ok());
+    }
+}
@@ -0,0 +1,101 @@
+#[cfg(test)]
+mod tests {
+    use backend::models::{BotLevel, Game, GameType, Move, Player, Position, SearchLimits};
+    use backend::solver::{find_vcf, find_vct, threat_move};
+    use backend::transposition::TranspositionTable;
+    use std::sync::atomic::AtomicBool;
+    use uuid::Uuid;
+
+    /// Stones far from the action, to keep the move count alternating.
+    const FILLER: [(usize, usize); 5] = [(0, 0), (14, 0), (0, 14), (14, 14), (2, 12)];
+
+    fn setup(x: &[(usize, usize)], o: &[(usize, usize)]) -> Game {
+        let mut game = Game::new(Uuid::new_v4(), Player::X, GameType::Normal, 15);
+        game.x = Some(Uuid::new_v4());
+        game.o = Some(Uuid::new_v4());
+        for (i, &(col, row)) in x.iter().enumerate() {
+            game.play(&Move::new(Player::X, Position::new(col, row)))
+                .unwrap();
+            if let Some(&(col, row)) = o.get(i) {
+                game.play(&Move::new(Player::O, Position::new(col, row)))
+                    .unwrap();
+            }
+        }
+        game
+    }
+
+    /// Plays `line` out and checks that it alternates and ends in a five for `attacker`.
+    fn assert_wins(mut game: Game, attacker: Player, line: &[Move]) {
+        for (i, mv) in line.iter().enumerate() {
+            let expected = if i % 2 == 0 {
+                attacker
+            } else {
+                attacker.opponent()
+            };
+            assert_eq!(mv.player, expected);
+            game.play(mv).unwrap();
+        }
+        let last = line.last().unwrap().position;
+        assert!(game.check_winning_move(&last).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_vcf_finds_double_four() {
+        let x = [(7, 7), (8, 7), (9, 7), (10, 8), (10, 9), (10, 10)];
+        let mut o = vec![(6, 7), (10, 11)];
+        o.extend(&FILLER[..4]);
+        let game = setup(&x, &o);
+
+        let line = find_vcf(&game, Player::X, 4).unwrap();
+        assert_eq!(line[0].position, Position::new(10, 7));
+        assert_wins(game.clone(), Player::X, &line);
+        assert!(find_vcf(&game, Player::O, 4).is_none());
+    }
+
+    #[test]
+    fn test_threat_move_stops_when_cancelled() {
+        let x = [(7, 7), (8, 7), (9, 7), (10, 8), (10, 9), (10, 10)];
+        let mut o = vec![(6, 7), (10, 11)];
+        o.extend(&FILLER[..4]);
+        let game = setup(&x, &o);
+        let settings = BotLevel::Expert.settings();
+
+        let running = AtomicBool::new(false);
+        let limits = SearchLimits::new(&settings, Player::X, &running);
+        assert_eq!(
+            threat_move(&game, Player::X, &settings, &limits),
+            Some(Position::new(10, 7))
+        );
+        let cancelled = AtomicBool::new(true);
+        let limits = SearchLimits::new(&settings, Player::X, &cancelled);
+        assert_eq!(threat_move(&game, Player::X, &settings, &limits), None);
+    }
+
+    #[test]
+    fn test_vct_finds_double_three() {
+        let x = [(7, 7), (8, 7), (9, 8), (9, 9)];
+        let game = setup(&x, &FILLER[..4]);
+
+        assert!(find_vcf(&game, Player::X, 4).is_none());
+        let line = find_vct(&game, Player::X, 3).unwrap();
+        assert_wins(game, Player::X, &line);
+    }
+
+    #[test]
+    fn test_bot_plays_its_forced_win() {
+        let mut x = vec![(6, 7), (10, 11)];
+        x.extend(&FILLER);
+        let o = [(7, 7), (8, 7), (9, 7), (10, 8), (10, 9), (10, 10)];
+        let mut game = setup(&x, &o);
+
+        let settings = BotLevel::Strong.settings();
+        let pos = game
+            .find_bot_move(
+                &settings,
+                &AtomicBool::new(false),
+                &TranspositionTable::default(),
+            )
+            .unwrap();
+        assert_eq!(pos, Position::new(10, 7));
+    }
+}
@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod tests {
+    use backend::models::{Player, Position, RuleSet};
+    use backend::tournament::{self, EngineConfig, Report, TournamentOptions};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    #[test]
+    fn test_engine_config_overrides() {
+        let config: EngineConfig = "strong:depth=2,time=50,blunder=0.5".parse().unwrap();
+        assert_eq!(config.settings.depth, 2);
+        assert_eq!(config.settings.time_budget, Duration::from_millis(50));
+        assert_eq!(config.settings.blunder_rate, 0.5);
+        assert!("grandmaster".parse::<EngineConfig>().is_err());
+        assert!("strong:speed=1".parse::<EngineConfig>().is_err());
+    }
+
+    #[test]
+    fn test_elo_of_even_and_lopsided_scores() {
+        let report = Report {
+            wins: 10,
+            losses: 10,
+            ..Default::default()
+        };
+        let (elo, low, high) = report.elo();
+        assert!(elo.abs() < 1e-9);
+        assert!(low < 0.0 && high > 0.0);
+
+        let report = Report {
+            wins: 75,
+            losses: 25,
+            ..Default::default()
+        };
+        assert!((report.elo().0 - 190.8).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_openings_are_distinct_and_colours_alternate() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let openings = tournament::random_openings(50, 2, 15, RuleSet::Freestyle, &mut rng);
+        assert_eq!(openings.iter().collect::<HashSet<_>>().len(), 50);
+
+        let engine: EngineConfig = "beginner:time=10,blunder=0".parse().unwrap();
+        let options = TournamentOptions {
+            games: 2,
+            board_size: 9,
+            rule_set: RuleSet::Freestyle,
+            opening_plies: 2,
+            jobs: 2,
+        };
+        let report = tournament::run(&engine, &engine, &options, &mut rng);
+        assert_eq!(report.played(), 2);
+        assert_eq!(report.games[0].a_plays, Player::X);
+        assert_eq!(report.games[1].a_plays, Player::O);
+        let opening = |game: usize| -> Vec<Position> {
+            report.games[game].moves[..2]
+                .iter()
+                .map(|mv| mv.position)
+                .collect()
+        };
+        assert_eq!(opening(0), opening(1));
+    }
+}
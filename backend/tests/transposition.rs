@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use backend::models::{Player, Position};
+    use backend::transposition::{self, Bound, TranspositionTable, TtEntry};
+
+    fn entry(key: u64, depth: i32, score: i32, bound: Bound) -> TtEntry {
+        TtEntry {
+            key,
+            depth,
+            score,
+            bound,
+            best_move: Some(Position::new(7, 7)),
+        }
+    }
+
+    #[test]
+    fn test_stone_keys_toggle() {
+        let pos = Position::new(3, 4);
+        let hash = transposition::stone_key(&pos, Player::X);
+        assert_ne!(hash, transposition::stone_key(&pos, Player::O));
+        assert_eq!(hash ^ transposition::stone_key(&pos, Player::X), 0);
+        assert_ne!(
+            transposition::node_key(hash, true, Player::X),
+            transposition::node_key(hash, false, Player::X)
+        );
+    }
+
+    #[test]
+    fn test_cutoff_respects_depth_and_bound() {
+        assert_eq!(entry(1, 2, 10, Bound::Exact).cutoff(2, -50, 50), Some(10));
+        assert_eq!(entry(1, 1, 10, Bound::Exact).cutoff(2, -50, 50), None);
+        assert_eq!(entry(1, 2, 60, Bound::Lower).cutoff(2, -50, 50), Some(60));
+        assert_eq!(entry(1, 2, 10, Bound::Lower).cutoff(2, -50, 50), None);
+        assert_eq!(entry(1, 2, -60, Bound::Upper).cutoff(2, -50, 50), Some(-60));
+        assert_eq!(entry(1, 2, 10, Bound::Upper).cutoff(2, -50, 50), None);
+    }
+
+    #[test]
+    fn test_store_keeps_deeper_entry() {
+        let tt = TranspositionTable::default();
+        assert!(tt.get(42).is_none());
+        tt.store(entry(42, 3, 10, Bound::Exact));
+        tt.store(entry(42, 1, 20, Bound::Exact));
+        assert_eq!(tt.get(42).map(|e| e.score), Some(10));
+        tt.store(entry(42, 4, 30, Bound::Exact));
+        assert_eq!(tt.get(42).map(|e| e.score), Some(30));
+        assert_eq!(tt.get(42).unwrap().best_move, Some(Position::new(7, 7)));
+        tt.store(TtEntry {
+            best_move: None,
+            ..entry(43, 2, -20, Bound::Upper)
+        });
+        let stored = tt.get(43).unwrap();
+        assert_eq!(
+            (stored.score, stored.bound, stored.best_move),
+            (-20, Bound::Upper, None)
+        );
+        tt.clear();
+        assert!(tt.get(42).is_none());
+    }
+}
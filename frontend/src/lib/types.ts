@@ -69,3 +69,7 @@ export type Message = {
 export type GameResponse = {
   room: string
 }
+
+export type MatchEvent =
+  | { event: 'Queued'; rating: number }
+  | { event: 'Matched'; room: string; colour: Player }